@@ -0,0 +1,251 @@
+use anyhow::Result;
+use git2::{ObjectType, Oid};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{index::FileIndex, module_tree::ModDeclaration};
+
+/// Version of the cache format, incremented whenever the extracted data changes.
+/// Caches written with another version, or by another version of the tool, are ignored.
+const CACHE_VERSION: u32 = 15;
+
+/// Name of the file storing the cache, in the cache directory.
+const CACHE_FILE: &str = "index.json";
+
+/// Name of the file storing the `mod` declarations of files, in the cache directory.
+const MODULES_FILE: &str = "modules.json";
+
+#[derive(Debug, Serialize, Deserialize)]
+/// Index of a file, with the hash of the content it was extracted from.
+///
+/// ## Properties:
+/// * `hash` (`String`): git blob id of the content of the file,
+/// * `scope` (`Vec<String>`): Scope of the file in its crate, which depends on the manifests,
+/// * `index` (`FileIndex`): Definitions, imports and references of the file.
+struct CachedFile {
+    /// git blob id of the content of the file.
+    hash: String,
+    /// Scope of the file in its crate, which depends on the manifests.
+    scope: Vec<String>,
+    /// Definitions, imports and references of the file.
+    index: FileIndex,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+/// Indexes of the files of a project, saved between runs so that only files whose content
+/// changed are parsed again.
+///
+/// ## Properties:
+/// * `version` (`String`): Version of the cache format and of the tool that wrote it,
+/// * `files` (`HashMap<PathBuf, CachedFile>`): Index of each file, by path relative to the
+///   project root.
+pub struct Cache {
+    /// Version of the cache format and of the tool that wrote it.
+    version: String,
+    /// Index of each file, by path relative to the project root.
+    files: HashMap<PathBuf, CachedFile>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+/// `mod` declarations of files, saved between runs so that the module tree is built without
+/// parsing the files whose content did not change.
+///
+/// ## Properties:
+/// * `version` (`String`): Version of the cache format and of the tool that wrote it,
+/// * `declarations` (`HashMap<String, Vec<ModDeclaration>>`): Declarations of each content, by
+///   git blob id.
+pub struct ModuleCache {
+    /// Version of the cache format and of the tool that wrote it.
+    version: String,
+    /// Declarations of each content, by git blob id.
+    declarations: HashMap<String, Vec<ModDeclaration>>,
+}
+
+/// Version written in the cache by this build of the tool.
+///
+/// ## Returns:
+/// * (`String`): Version of the cache format followed by the version of the tool.
+fn current_version() -> String {
+    format!("{CACHE_VERSION}-{}", env!("CARGO_PKG_VERSION"))
+}
+
+/// Directory where the cache of a project is stored, in the target directory of cargo.
+/// `CARGO_TARGET_DIR` is relative to the current directory, as cargo resolves it.
+///
+/// ## Parameters:
+/// * `project_root` (`&std::path::Path`): Root of the project.
+///
+/// ## Returns:
+/// * (`std::path::PathBuf`): `impact-scanner/` under `CARGO_TARGET_DIR`, or under `target/` in
+///   the project root.
+pub fn cache_dir(project_root: &Path) -> PathBuf {
+    let target_dir = std::env::var_os("CARGO_TARGET_DIR")
+        .filter(|dir| !dir.is_empty())
+        .and_then(|dir| std::path::absolute(dir).ok())
+        .unwrap_or_else(|| project_root.join("target"));
+    target_dir.join("impact-scanner")
+}
+
+/// Hashes the content of a file the way git does for blobs.
+///
+/// ## Parameters:
+/// * `source` (`&str`): Content of the file.
+///
+/// ## Returns:
+/// * (`Result<String>`): git blob id of the content.
+pub fn content_hash(source: &str) -> Result<String> {
+    Ok(Oid::hash_object(ObjectType::Blob, source.as_bytes())?.to_string())
+}
+
+/// Removes the cache of a project.
+///
+/// ## Parameters:
+/// * `project_root` (`&std::path::Path`): Root of the project.
+///
+/// ## Returns:
+/// * (`Result<bool>`): true if a cache was removed, false if there was none.
+pub fn clear(project_root: &Path) -> Result<bool> {
+    let dir = cache_dir(project_root);
+    if !dir.exists() {
+        return Ok(false);
+    }
+    fs::remove_dir_all(dir)?;
+    Ok(true)
+}
+
+impl Cache {
+    /// Creates an empty cache for the current version of the tool.
+    ///
+    /// ## Returns:
+    /// * (`Cache`): Cache without any file.
+    pub fn new() -> Self {
+        Cache {
+            version: current_version(),
+            files: HashMap::new(),
+        }
+    }
+
+    /// Loads the cache of a project.
+    /// A missing, unreadable or outdated cache gives an empty cache.
+    ///
+    /// ## Parameters:
+    /// * `project_root` (`&std::path::Path`): Root of the project.
+    ///
+    /// ## Returns:
+    /// * (`Cache`): Cached indexes of the files of the project.
+    pub fn load(project_root: &Path) -> Self {
+        fs::read_to_string(cache_dir(project_root).join(CACHE_FILE))
+            .ok()
+            .and_then(|content| serde_json::from_str::<Cache>(&content).ok())
+            .filter(|cache| cache.version == current_version())
+            .unwrap_or_else(Cache::new)
+    }
+
+    /// Gets the cached index of a file, if neither its content nor its scope changed.
+    ///
+    /// ## Parameters:
+    /// * `file` (`&std::path::Path`): Path of the file, relative to the project root,
+    /// * `hash` (`&str`): Hash of the current content of the file,
+    /// * `scope` (`&[String]`): Current scope of the file in its crate.
+    ///
+    /// ## Returns:
+    /// * (`Option<&FileIndex>`): Cached index, None if the file is not cached or changed.
+    pub fn get(&self, file: &Path, hash: &str, scope: &[String]) -> Option<&FileIndex> {
+        self.files
+            .get(file)
+            .filter(|cached| cached.hash == hash && cached.scope == scope)
+            .map(|cached| &cached.index)
+    }
+
+    /// Stores the index of a file.
+    ///
+    /// ## Parameters:
+    /// * `file` (`std::path::PathBuf`): Path of the file, relative to the project root,
+    /// * `hash` (`String`): Hash of the content the index was extracted from,
+    /// * `scope` (`Vec<String>`): Scope of the file in its crate,
+    /// * `index` (`FileIndex`): Index of the file.
+    pub fn insert(&mut self, file: PathBuf, hash: String, scope: Vec<String>, index: FileIndex) {
+        self.files.insert(file, CachedFile { hash, scope, index });
+    }
+
+    /// Writes the cache in the cache directory of a project.
+    ///
+    /// ## Parameters:
+    /// * `project_root` (`&std::path::Path`): Root of the project.
+    ///
+    /// ## Returns:
+    /// * (`Result<()>`): Error if the cache could not be written.
+    pub fn save(&self, project_root: &Path) -> Result<()> {
+        let dir = cache_dir(project_root);
+        fs::create_dir_all(&dir)?;
+        fs::write(dir.join(CACHE_FILE), serde_json::to_string(self)?)?;
+        Ok(())
+    }
+}
+
+impl ModuleCache {
+    /// Creates an empty cache of `mod` declarations for the current version of the tool.
+    ///
+    /// ## Returns:
+    /// * (`ModuleCache`): Cache without any declaration.
+    pub fn new() -> Self {
+        ModuleCache {
+            version: current_version(),
+            declarations: HashMap::new(),
+        }
+    }
+
+    /// Loads the cached `mod` declarations of a project.
+    /// A missing, unreadable or outdated cache gives an empty cache.
+    ///
+    /// ## Parameters:
+    /// * `project_root` (`&std::path::Path`): Root of the project.
+    ///
+    /// ## Returns:
+    /// * (`ModuleCache`): Cached declarations of the files of the project.
+    pub fn load(project_root: &Path) -> Self {
+        fs::read_to_string(cache_dir(project_root).join(MODULES_FILE))
+            .ok()
+            .and_then(|content| serde_json::from_str::<ModuleCache>(&content).ok())
+            .filter(|cache| cache.version == current_version())
+            .unwrap_or_else(ModuleCache::new)
+    }
+
+    /// Gets the cached `mod` declarations of a content.
+    ///
+    /// ## Parameters:
+    /// * `hash` (`&str`): git blob id of the content.
+    ///
+    /// ## Returns:
+    /// * (`Option<&Vec<ModDeclaration>>`): Cached declarations, None if the content is not cached.
+    pub fn get(&self, hash: &str) -> Option<&Vec<ModDeclaration>> {
+        self.declarations.get(hash)
+    }
+
+    /// Stores the `mod` declarations of a content.
+    ///
+    /// ## Parameters:
+    /// * `hash` (`String`): git blob id of the content,
+    /// * `declarations` (`Vec<ModDeclaration>`): Declarations extracted from the content.
+    pub fn insert(&mut self, hash: String, declarations: Vec<ModDeclaration>) {
+        self.declarations.insert(hash, declarations);
+    }
+
+    /// Writes the cached `mod` declarations in the cache directory of a project.
+    ///
+    /// ## Parameters:
+    /// * `project_root` (`&std::path::Path`): Root of the project.
+    ///
+    /// ## Returns:
+    /// * (`Result<()>`): Error if the cache could not be written.
+    pub fn save(&self, project_root: &Path) -> Result<()> {
+        let dir = cache_dir(project_root);
+        fs::create_dir_all(&dir)?;
+        fs::write(dir.join(MODULES_FILE), serde_json::to_string(self)?)?;
+        Ok(())
+    }
+}
@@ -1,39 +1,406 @@
-use git2::{DiffDelta, DiffHunk, DiffLine, DiffOptions, Repository};
-use std::{collections::HashMap, path::PathBuf};
+use anyhow::{anyhow, bail, Result};
+use git2::{
+    Delta, Diff, DiffDelta, DiffFindOptions, DiffHunk, DiffLine, DiffOptions, ObjectType, Oid,
+    Repository, RevparseMode, Tree, TreeWalkMode, TreeWalkResult,
+};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use crate::{cache, index};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// Changes to analyze in a git repository.
+pub enum DiffSource {
+    /// Changes staged in the index, compared to HEAD.
+    Staged,
+    /// Changes of the working tree not staged yet, compared to the index.
+    Worktree,
+    /// Changes of HEAD since it diverged from the given revision (as `rev...HEAD`).
+    Base(String),
+    /// Changes between two revisions, as `a..b`, or `a...b` to start from their merge base.
+    Range(String),
+    /// Changes introduced by a single commit, compared to its first parent.
+    Commit(String),
+}
+
+/// Finds the root of the working tree of the git repository containing a path.
+/// Every path handled by the analysis is relative to this root.
+///
+/// ## Parameters:
+/// * `path` (`&std::path::Path`): Any path inside the repository, eg. one of its subdirectories.
+///
+/// ## Returns:
+/// * (`Result<std::path::PathBuf>`): Absolute path of the root of the working tree. Fails if no
+///   repository contains the path, or if the repository is bare.
+pub fn discover_root(path: &Path) -> Result<PathBuf> {
+    let repo = Repository::discover(path)?;
+    let Some(workdir) = repo.workdir() else {
+        bail!("repository {:?} has no working tree", repo.path())
+    };
+    Ok(workdir.canonicalize()?)
+}
+
+/// Returns the tree of the commit with the given id.
+///
+/// ## Parameters:
+/// * `repo` (`&git2::Repository`): Repository containing the commit,
+/// * `oid` (`git2::Oid`): Id of the commit.
+///
+/// ## Returns:
+/// * (`Result<git2::Tree>`): Tree of the commit.
+fn tree_of(repo: &Repository, oid: Oid) -> Result<Tree<'_>> {
+    Ok(repo.find_commit(oid)?.tree()?)
+}
+
+/// Returns the trees to compare for a revision range (`a..b` or `a...b`).
+///
+/// ## Parameters:
+/// * `repo` (`&git2::Repository`): Repository to resolve the range in,
+/// * `range` (`&str`): Revision range.
+///
+/// ## Returns:
+/// * (`Result<(git2::Tree, git2::Tree)>`): Old and new trees of the range.
+fn trees_of_range<'a>(repo: &'a Repository, range: &str) -> Result<(Tree<'a>, Tree<'a>)> {
+    let revspec = repo.revparse(range)?;
+    let (Some(from), Some(to)) = (revspec.from(), revspec.to()) else {
+        return Err(anyhow!("{range:?} is not a range of revisions"));
+    };
+    let from = from.peel_to_commit()?.id();
+    let to = to.peel_to_commit()?.id();
+    let from = if revspec.mode().contains(RevparseMode::MERGE_BASE) {
+        repo.merge_base(from, to)?
+    } else {
+        from
+    };
+    Ok((tree_of(repo, from)?, tree_of(repo, to)?))
+}
+
+/// Version of the repository on one side of a diff.
+enum Side<'a> {
+    /// Content of a commit.
+    Tree(Tree<'a>),
+    /// Content staged in the index.
+    Index,
+    /// Content of the working tree.
+    Workdir,
+    /// Nothing, eg. before the root commit.
+    Empty,
+}
+
+/// Returns the old and new versions of the repository to compare for a source of changes.
+///
+/// ## Parameters:
+/// * `repo` (`&git2::Repository`): Repository to compare versions of,
+/// * `source` (`&DiffSource`): Changes to consider.
+///
+/// ## Returns:
+/// * (`Result<(Side, Side)>`): Old and new versions of the repository.
+fn sides<'a>(repo: &'a Repository, source: &DiffSource) -> Result<(Side<'a>, Side<'a>)> {
+    let sides = match source {
+        DiffSource::Staged => (Side::Tree(repo.head()?.peel_to_tree()?), Side::Index),
+        DiffSource::Worktree => (Side::Index, Side::Workdir),
+        DiffSource::Base(base) => {
+            let head = repo.head()?.peel_to_commit()?.id();
+            let base = repo.revparse_single(base)?.peel_to_commit()?.id();
+            (
+                Side::Tree(tree_of(repo, repo.merge_base(base, head)?)?),
+                Side::Tree(tree_of(repo, head)?),
+            )
+        }
+        DiffSource::Range(range) => {
+            let (from, to) = trees_of_range(repo, range)?;
+            (Side::Tree(from), Side::Tree(to))
+        }
+        DiffSource::Commit(revision) => {
+            let commit = repo.revparse_single(revision)?.peel_to_commit()?;
+            let parent = match commit.parent_count() {
+                0 => Side::Empty,
+                _ => Side::Tree(commit.parent(0)?.tree()?),
+            };
+            (parent, Side::Tree(commit.tree()?))
+        }
+    };
+    Ok(sides)
+}
+
+/// Computes the diff between two versions of the repository.
+///
+/// ## Parameters:
+/// * `repo` (`&git2::Repository`): Repository to compute the diff in,
+/// * `old` (`&Side`): Old version of the repository,
+/// * `new` (`&Side`): New version of the repository,
+/// * `options` (`&mut git2::DiffOptions`): Options of the diff.
+///
+/// ## Returns:
+/// * (`Result<git2::Diff>`): Diff between the two versions.
+fn get_diff<'a>(
+    repo: &'a Repository,
+    old: &Side<'a>,
+    new: &Side<'a>,
+    options: &mut DiffOptions,
+) -> Result<Diff<'a>> {
+    let old_tree = match old {
+        Side::Tree(tree) => Some(tree),
+        _ => None,
+    };
+    let diff = match new {
+        Side::Index => repo.diff_tree_to_index(old_tree, Some(&repo.index()?), Some(options))?,
+        Side::Workdir => {
+            options
+                .include_untracked(true)
+                .recurse_untracked_dirs(true)
+                .show_untracked_content(true);
+            repo.diff_index_to_workdir(None, Some(options))?
+        }
+        Side::Tree(tree) => repo.diff_tree_to_tree(old_tree, Some(tree), Some(options))?,
+        Side::Empty => bail!("can not compute a diff towards an empty version"),
+    };
+    Ok(diff)
+}
+
+/// Reads the content of a file in a version of the repository.
+///
+/// ## Parameters:
+/// * `repo` (`&git2::Repository`): Repository containing the file,
+/// * `side` (`&Side`): Version of the repository to read the file from,
+/// * `file` (`&std::path::Path`): Path of the file, relative to the root of the repository.
+///
+/// ## Returns:
+/// * (`Result<Option<String>>`): Content of the file, None if it does not exist in this version.
+fn read_file(repo: &Repository, side: &Side, file: &Path) -> Result<Option<String>> {
+    let blob_id = match side {
+        Side::Tree(tree) => match tree.get_path(file) {
+            Ok(entry) => entry.id(),
+            Err(_) => return Ok(None),
+        },
+        Side::Index => match repo.index()?.get_path(file, 0) {
+            Some(entry) => entry.id,
+            None => return Ok(None),
+        },
+        Side::Workdir => {
+            let Some(workdir) = repo.workdir() else {
+                bail!("repository has no working tree")
+            };
+            let path = workdir.join(file);
+            if !path.exists() {
+                return Ok(None);
+            }
+            return Ok(Some(std::fs::read_to_string(path)?));
+        }
+        Side::Empty => return Ok(None),
+    };
+    let blob = repo.find_blob(blob_id)?;
+    Ok(Some(String::from_utf8_lossy(blob.content()).into_owned()))
+}
+
+/// Reads the content of a file before the analyzed changes.
+///
+/// ## Parameters:
+/// * `path` (`&std::path::Path`): Root of the working tree of the git repository,
+/// * `source` (`&DiffSource`): Changes to consider,
+/// * `file` (`&std::path::Path`): Path of the file, relative to the root of the repository.
+///
+/// ## Returns:
+/// * (`Result<Option<String>>`): Content of the file, None if it did not exist.
+pub fn read_old_file(path: &Path, source: &DiffSource, file: &Path) -> Result<Option<String>> {
+    let repo = Repository::open(path)?;
+    let (old, _new) = sides(&repo, source)?;
+    read_file(&repo, &old, file)
+}
+
+/// Reads the content of a file after the analyzed changes.
+/// Depending on the source of changes, the file is read from the index, a commit or the working
+/// tree, so that its content is the one the changed lines refer to.
+///
+/// ## Parameters:
+/// * `path` (`&std::path::Path`): Root of the working tree of the git repository,
+/// * `source` (`&DiffSource`): Changes to consider,
+/// * `file` (`&std::path::Path`): Path of the file, relative to the root of the repository.
+///
+/// ## Returns:
+/// * (`Result<Option<String>>`): Content of the file, None if it does not exist anymore.
+pub fn read_new_file(path: &Path, source: &DiffSource, file: &Path) -> Result<Option<String>> {
+    let repo = Repository::open(path)?;
+    let (_old, new) = sides(&repo, source)?;
+    read_file(&repo, &new, file)
+}
+
+/// Lists the files of a version of the repository.
+///
+/// ## Parameters:
+/// * `repo` (`&git2::Repository`): Repository containing the files,
+/// * `side` (`&Side`): Version of the repository to list the files of.
+///
+/// ## Returns:
+/// * (`Result<Option<Vec<PathBuf>>>`): Paths of the files, relative to the root of the
+///   repository, None for the working tree, whose files can be listed directly.
+fn list_files(repo: &Repository, side: &Side) -> Result<Option<Vec<PathBuf>>> {
+    let files = match side {
+        Side::Workdir => return Ok(None),
+        Side::Empty => Vec::new(),
+        Side::Index => repo
+            .index()?
+            .iter()
+            .map(|entry| PathBuf::from(String::from_utf8_lossy(&entry.path).into_owned()))
+            .collect(),
+        Side::Tree(tree) => {
+            let mut files = Vec::new();
+            tree.walk(TreeWalkMode::PreOrder, |dir, entry| {
+                if let (Some(ObjectType::Blob), Some(name)) = (entry.kind(), entry.name()) {
+                    files.push(Path::new(dir).join(name));
+                }
+                TreeWalkResult::Ok
+            })?;
+            files
+        }
+    };
+    Ok(Some(files))
+}
+
+/// Lists the files of the repository after the analyzed changes.
+///
+/// ## Parameters:
+/// * `path` (`&std::path::Path`): Root of the working tree of the git repository,
+/// * `source` (`&DiffSource`): Changes to consider.
+///
+/// ## Returns:
+/// * (`Result<Option<Vec<PathBuf>>>`): Paths of the files, relative to the root of the
+///   repository, None if the changes end in the working tree, whose files can be listed
+///   directly.
+pub fn list_new_files(path: &Path, source: &DiffSource) -> Result<Option<Vec<PathBuf>>> {
+    let repo = Repository::open(path)?;
+    let (_old, new) = sides(&repo, source)?;
+    list_files(&repo, &new)
+}
+
+/// Reads the content of every file of the repository after the analyzed changes, so that files
+/// are indexed in the version the changed lines refer to.
+///
+/// ## Parameters:
+/// * `path` (`&std::path::Path`): Root of the working tree of the git repository,
+/// * `source` (`&DiffSource`): Changes to consider,
+/// * `filter` (`impl Fn(&Path) -> bool`): Returns true for the files to read, given their path
+///   relative to the root of the repository.
+///
+/// ## Returns:
+/// * (`Result<Option<Vec<(PathBuf, String)>>>`): Path and content of the files, None if the
+///   changes end in the working tree, whose files can be read directly.
+pub fn read_new_files(
+    path: &Path,
+    source: &DiffSource,
+    filter: impl Fn(&Path) -> bool,
+) -> Result<Option<Vec<(PathBuf, String)>>> {
+    let repo = Repository::open(path)?;
+    let (_old, new) = sides(&repo, source)?;
+    let Some(files) = list_files(&repo, &new)? else {
+        return Ok(None);
+    };
+    let mut sources = Vec::new();
+    for file in files.into_iter().filter(|file| filter(file)) {
+        if let Some(content) = read_file(&repo, &new, &file)? {
+            sources.push((file, content));
+        }
+    }
+    Ok(Some(sources))
+}
+
+#[derive(Debug, Default, Clone)]
+/// Lines of a file changed by a diff.
+///
+/// ## Properties:
+/// * `added_lines` (`Vec<usize>`): Lines added or modified, numbered in the new version,
+/// * `removed_lines` (`Vec<usize>`): Lines removed or modified, numbered in the old version,
+/// * `old_path` (`Option<PathBuf>`): Path of the file in the old version, None if it was added.
+///   Differs from the new path if the file was renamed or moved,
+/// * `is_deleted` (`bool`): true iff the file does not exist in the new version.
+pub struct FileChanges {
+    /// Lines added or modified, numbered in the new version of the file.
+    pub added_lines: Vec<usize>,
+    /// Lines removed or modified, numbered in the old version of the file.
+    pub removed_lines: Vec<usize>,
+    /// Path of the file in the old version, None if it was added.
+    /// Differs from the new path if the file was renamed or moved.
+    pub old_path: Option<PathBuf>,
+    /// true iff the file does not exist in the new version.
+    pub is_deleted: bool,
+}
 
 /// Returns the lines that changed in a git repository.
 ///
+/// Files ignored by git, hidden files and the files of `target` directories are left out, as they
+/// are left out of the index. So is the cache of the tool, which may be in the working tree.
+///
 /// ## Parameters:
-/// * `path` (`&std::path::PathBuf`): Path to the git repository.
+/// * `path` (`&std::path::Path`): Root of the working tree of the git repository,
+/// * `source` (`&DiffSource`): Changes to consider.
 ///
 /// ## Returns:
-/// * (`anyhow::Result<std::collections::HashMap<std::path::PathBuf, Vec<usize>>>`): Map associating file names
-/// to a list of changed lines in git repository. Line numbers are lines in the staged version of
-/// the repo.
-pub fn get_changed_lines(path: &PathBuf) -> anyhow::Result<HashMap<PathBuf, Vec<usize>>> {
+/// * (`anyhow::Result<std::collections::HashMap<std::path::PathBuf, FileChanges>>`): Map
+///   associating file names to their changed lines. Files are named after their new version,
+///   or their old version if they were deleted.
+pub fn get_changed_lines(
+    path: &Path,
+    source: &DiffSource,
+) -> anyhow::Result<HashMap<PathBuf, FileChanges>> {
     let repo = Repository::open(path)?;
-    let index = repo.index()?;
-    let head = repo.head()?.peel_to_tree()?;
-    let diff = repo.diff_tree_to_index(Some(&head), Some(&index), Some(&mut DiffOptions::new()))?;
-    let mut result: HashMap<PathBuf, Vec<usize>> = HashMap::new();
+    let (old, new) = sides(&repo, source)?;
+    let mut diff = get_diff(&repo, &old, &new, &mut DiffOptions::new())?;
+    diff.find_similar(Some(
+        DiffFindOptions::new()
+            .renames(true)
+            .copies(true)
+            .for_untracked(true),
+    ))?;
+    let mut result: HashMap<PathBuf, FileChanges> = HashMap::new();
+    for delta in diff.deltas() {
+        let Some(path) = delta.new_file().path().or(delta.old_file().path()) else {
+            continue;
+        };
+        let changes = result.entry(path.to_path_buf()).or_default();
+        // A copied file is new, its source is still present and unchanged.
+        if !matches!(
+            delta.status(),
+            Delta::Added | Delta::Untracked | Delta::Copied
+        ) {
+            changes.old_path = delta.old_file().path().map(Path::to_path_buf);
+        }
+        changes.is_deleted = delta.status() == Delta::Deleted;
+    }
     diff.foreach(
         &mut |_delta, _progress| true,
         None,
         None,
         Some(
             &mut |delta: DiffDelta, _hunk: Option<DiffHunk>, line: DiffLine| {
-                if line.origin() == '+' {
-                    if let Some(path) = delta.new_file().path() {
+                let Some(path) = delta.new_file().path().or(delta.old_file().path()) else {
+                    return true;
+                };
+                let changes = result.entry(path.to_path_buf()).or_default();
+                match line.origin() {
+                    '+' => {
                         let line_num = line.new_lineno().unwrap_or(0) as usize;
                         if line_num > 0 {
-                            result.entry(path.to_path_buf()).or_default().push(line_num);
+                            changes.added_lines.push(line_num);
+                        }
+                    }
+                    '-' => {
+                        let line_num = line.old_lineno().unwrap_or(0) as usize;
+                        if line_num > 0 {
+                            changes.removed_lines.push(line_num);
                         }
                     }
+                    _ => (),
                 }
                 true
             },
         ),
     )?;
+    let cache_dir = cache::cache_dir(path);
+    result.retain(|file, _| {
+        !index::is_ignored_path(file) && !path.join(file).starts_with(&cache_dir)
+    });
 
     Ok(result)
 }
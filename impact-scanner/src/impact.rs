@@ -0,0 +1,257 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use crate::{
+    index::SymbolIndex, language::get_language_for_file, symbol::Symbol,
+    symbol_status::SymbolStatus, usage,
+};
+
+#[derive(Debug, Default)]
+/// Symbols affected by a set of changed symbols.
+///
+/// ## Properties:
+/// * `changed` (`Vec<Symbol>`): Symbols the analysis started from,
+/// * `edges` (`HashMap<Symbol, HashSet<Symbol>>`): Maps each affected symbol to the changed or
+///   affected symbols it uses.
+pub struct ImpactGraph {
+    /// Symbols the analysis started from.
+    pub changed: Vec<Symbol>,
+    /// Maps each affected symbol to the changed or affected symbols it uses.
+    pub edges: HashMap<Symbol, HashSet<Symbol>>,
+}
+
+impl ImpactGraph {
+    /// Symbols affected by the changes, excluding the changed symbols themselves.
+    ///
+    /// ## Returns:
+    /// * (`Vec<&Symbol>`): Affected symbols, sorted by file and line.
+    pub fn affected(&self) -> Vec<&Symbol> {
+        let mut affected: Vec<&Symbol> = self
+            .edges
+            .keys()
+            .filter(|symbol| !self.changed.contains(symbol))
+            .collect();
+        affected.sort_by(|a, b| (&a.file, a.line).cmp(&(&b.file, b.line)));
+        affected
+    }
+
+    /// Symbols directly used by the given symbol that are changed or affected.
+    ///
+    /// ## Parameters:
+    /// * `symbol` (`&Symbol`): Symbol to get the dependencies of.
+    ///
+    /// ## Returns:
+    /// * (`Vec<&Symbol>`): Changed or affected symbols used by `symbol`.
+    pub fn uses(&self, symbol: &Symbol) -> Vec<&Symbol> {
+        self.edges
+            .get(symbol)
+            .map(|used| used.iter().collect())
+            .unwrap_or_default()
+    }
+}
+
+/// Computes the symbols transitively affected by the given changed symbols.
+/// Callers of every changed or affected symbol are added until a fixed point is reached.
+/// Implementations of a changed trait item are affected, and trait items are affected by their
+/// implementations.
+///
+/// ## Parameters:
+/// * `index` (`&SymbolIndex`): Index of the analyzed project,
+/// * `changed` (`&[Symbol]`): Symbols that changed,
+/// * `strict_methods` (`bool`): true to ignore method calls on values of unknown type.
+///
+/// ## Returns:
+/// * (`ImpactGraph`): Graph linking each affected symbol to the symbols it uses.
+pub fn compute_impact(
+    index: &SymbolIndex,
+    changed: &[Symbol],
+    strict_methods: bool,
+) -> ImpactGraph {
+    let mut graph = ImpactGraph {
+        changed: changed.to_vec(),
+        edges: HashMap::new(),
+    };
+    let mut visited: HashSet<Symbol> = changed.iter().cloned().collect();
+    let mut queue: VecDeque<Symbol> = changed.iter().cloned().collect();
+
+    while let Some(used) = queue.pop_front() {
+        let language = get_language_for_file(&used.file, &index.workspace);
+        let mut callers: Vec<&Symbol> =
+            usage::find_symbol_usages(index, &used, &language, strict_methods)
+                .iter()
+                .flat_map(|usage| index.enclosing_symbols(&usage.file, usage.line))
+                .collect();
+        // Implementations must follow a changed item of their trait, and uses through the trait
+        // reach the implementations of its items.
+        if used.status != SymbolStatus::Unchanged {
+            callers.extend(index.implementations_of(&used));
+        }
+        callers.extend(index.trait_items_of(&used));
+        for caller in callers {
+            if *caller == used {
+                continue;
+            }
+            graph
+                .edges
+                .entry(caller.clone())
+                .or_default()
+                .insert(used.clone());
+            if visited.insert(caller.clone()) {
+                queue.push_back(caller.clone());
+            }
+        }
+    }
+    graph
+}
+
+#[cfg(test)]
+mod tests {
+    use super::compute_impact;
+    use crate::{index::SymbolIndex, symbol_status::SymbolStatus};
+
+    /// Computes the symbols affected by the modification of a symbol of an index.
+    ///
+    /// ## Parameters:
+    /// * `index` (`&SymbolIndex`): Index of the project,
+    /// * `scope` (`&str`): Scope of the modified symbol, as `crate::module::name`.
+    ///
+    /// ## Returns:
+    /// * (`Vec<String>`): Scopes of the affected symbols, sorted.
+    fn affected_by(index: &SymbolIndex, scope: &str) -> Vec<String> {
+        let mut changed = index.symbol(scope);
+        changed.status = SymbolStatus::Modified;
+        let graph = compute_impact(index, &[changed], false);
+        let mut affected: Vec<String> = graph
+            .affected()
+            .into_iter()
+            .map(|symbol| symbol.scope.join("::"))
+            .collect();
+        affected.sort();
+        affected
+    }
+
+    #[test]
+    fn same_named_items_of_other_modules_are_not_affected() {
+        let index = SymbolIndex::from_sources(&[(
+            "src/lib.rs",
+            r#"pub fn helper() -> u32 { 1 }
+pub mod other {
+    pub fn helper() -> u32 { 2 }
+    pub fn use_other() -> u32 { helper() }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn helper_works() { assert_eq!(helper(), 1); }
+    #[test]
+    fn other_works() { assert_eq!(other::use_other(), 2); }
+}
+"#,
+        )]);
+        assert_eq!(
+            affected_by(&index, "crate::helper"),
+            vec!["crate::tests::helper_works"]
+        );
+        assert_eq!(
+            affected_by(&index, "crate::other::helper"),
+            vec!["crate::other::use_other", "crate::tests::other_works"]
+        );
+    }
+
+    #[test]
+    fn methods_of_other_implementations_are_not_affected() {
+        let index = SymbolIndex::from_sources(&[(
+            "src/lib.rs",
+            r#"pub struct A;
+pub struct B;
+impl A {
+    pub fn new() -> Self { A }
+}
+impl B {
+    pub fn new() -> Self { B }
+}
+pub fn make_a() -> A { A::new() }
+pub fn make_b() -> B { B::new() }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn a_works() { make_a(); }
+    #[test]
+    fn b_works() { make_b(); }
+}
+"#,
+        )]);
+        assert_eq!(
+            affected_by(&index, "crate::A::new"),
+            vec!["crate::make_a", "crate::tests::a_works"]
+        );
+        assert_eq!(
+            affected_by(&index, "crate::B::new"),
+            vec!["crate::make_b", "crate::tests::b_works"]
+        );
+    }
+
+    #[test]
+    fn users_of_reexports_are_affected() {
+        let index = SymbolIndex::from_sources(&[
+            (
+                "src/lib.rs",
+                r#"mod parser;
+pub use parser::parse;
+"#,
+            ),
+            ("src/parser.rs", "pub fn parse() {}\n"),
+            (
+                "src/cli.rs",
+                r#"use crate::parse;
+pub fn main() { parse(); }
+#[test]
+fn main_works() { main(); }
+"#,
+            ),
+        ]);
+        assert_eq!(
+            affected_by(&index, "crate::parser::parse"),
+            vec!["crate::cli::main", "crate::cli::main_works"]
+        );
+    }
+
+    #[test]
+    fn trait_dispatch_reaches_implementations() {
+        let index = SymbolIndex::from_sources(&[(
+            "src/lib.rs",
+            r#"pub trait Shape {
+    fn area(&self) -> f64;
+}
+pub struct Circle;
+impl Shape for Circle {
+    fn area(&self) -> f64 { 3.14 }
+}
+pub struct Square;
+impl Shape for Square {
+    fn area(&self) -> f64 { 1.0 }
+}
+pub fn total(shapes: &[&dyn Shape]) -> f64 {
+    shapes.iter().map(|shape| shape.area()).sum()
+}
+"#,
+        )]);
+        // Changing an implementation affects the uses through the trait.
+        assert_eq!(
+            affected_by(&index, "crate::<Circle as Shape>::area"),
+            vec!["crate::Shape::area", "crate::total"]
+        );
+        // Changing the trait affects every implementation.
+        assert_eq!(
+            affected_by(&index, "crate::Shape::area"),
+            vec![
+                "crate::<Circle as Shape>",
+                "crate::<Circle as Shape>::area",
+                "crate::<Square as Shape>",
+                "crate::<Square as Shape>::area",
+                "crate::total",
+            ]
+        );
+    }
+}
@@ -0,0 +1,563 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+use walkdir::{DirEntry, WalkDir};
+
+use crate::{
+    cache::{self, Cache},
+    git,
+    language::{
+        get_language_for_file, is_known_language, parsable_language::ParsableLanguage, Languages,
+    },
+    locals,
+    reexport::Reexports,
+    symbol::{self, Definition, Symbol},
+    symbol_kind::SymbolKind,
+    usage,
+    workspace::Workspace,
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Identifier used in a file, resolved with the imports of the file.
+///
+/// ## Properties:
+/// * `symbol` (`Symbol`): Used symbol, with the scope it was resolved to,
+/// * `is_unresolved` (`bool`): true iff the first segment of the identifier is neither imported
+///   explicitly nor defined in the module of the identifier, and may come from a glob import.
+pub struct Reference {
+    /// Used symbol, with the scope it was resolved to.
+    pub symbol: Symbol,
+    /// true iff the first segment of the identifier may come from a glob import.
+    pub is_unresolved: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+/// Symbols defined, imported and used in a source file.
+///
+/// ## Properties:
+/// * `definitions` (`Vec<Definition>`): Symbols defined in the file,
+/// * `use_map` (`HashMap<String, Symbol>`): Symbols imported in the file, by name,
+/// * `globs` (`Vec<Symbol>`): Modules whose items are all imported in the file,
+/// * `references` (`Vec<Reference>`): Identifiers used in the file.
+pub struct FileIndex {
+    /// Symbols defined in the file.
+    pub definitions: Vec<Definition>,
+    /// Symbols imported in the file, by name.
+    pub use_map: HashMap<String, Symbol>,
+    /// Modules whose items are all imported in the file.
+    pub globs: Vec<Symbol>,
+    /// Identifiers used in the file.
+    pub references: Vec<Reference>,
+}
+
+#[derive(Debug, Default)]
+/// Definitions, imports and references of every source file of a project, built in a single
+/// pass so that usages of symbols can be looked up without parsing files again.
+///
+/// ## Properties:
+/// * `files` (`HashMap<PathBuf, FileIndex>`): Index of each source file, by path relative to the
+///   project root,
+/// * `workspace` (`Rc<Workspace>`): Packages of the project,
+/// * `reexports` (`Reexports`): Paths under which symbols are re-exported.
+pub struct SymbolIndex {
+    /// Index of each source file, by path relative to the project root.
+    pub files: HashMap<PathBuf, FileIndex>,
+    /// Packages of the project.
+    pub workspace: Rc<Workspace>,
+    /// Paths under which symbols are re-exported.
+    pub reexports: Reexports,
+}
+
+/// Returns true iff a directory entry should be skipped when indexing a project.
+/// Hidden entries (eg. `.git`) and the `target` directory of cargo are skipped.
+///
+/// ## Parameters:
+/// * `entry` (`&walkdir::DirEntry`): Entry of the project directory.
+///
+/// ## Returns:
+/// * (`bool`): true iff the entry should not be indexed.
+pub fn is_ignored(entry: &DirEntry) -> bool {
+    let name = entry.file_name().to_string_lossy();
+    entry.depth() > 0 && (name.starts_with('.') || (entry.file_type().is_dir() && name == "target"))
+}
+
+/// Returns true iff a file should be skipped when indexing a project, given its path relative to
+/// the project root, as `is_ignored` does for the entries of the working tree.
+///
+/// ## Parameters:
+/// * `file` (`&std::path::Path`): Path of the file, relative to the project root.
+///
+/// ## Returns:
+/// * (`bool`): true iff the file or one of its directories should not be indexed.
+pub fn is_ignored_path(file: &Path) -> bool {
+    let components: Vec<_> = file.components().collect();
+    components.iter().enumerate().any(|(position, component)| {
+        let name = component.as_os_str().to_string_lossy();
+        name.starts_with('.') || (position + 1 < components.len() && name == "target")
+    })
+}
+
+/// Reads the source files of a project whose language is known, in the version of the project
+/// after the analyzed changes: the index for staged changes, a commit for revisions, and the
+/// working tree otherwise.
+/// They are read once, for the module tree, the changed symbols and the index.
+///
+/// ## Parameters:
+/// * `project_root` (`&std::path::Path`): Root of the project,
+/// * `diff_source` (`&git::DiffSource`): Analyzed changes.
+///
+/// ## Returns:
+/// * (`HashMap<PathBuf, String>`): Content of each file, by path relative to the project root.
+pub fn read_sources(
+    project_root: &Path,
+    diff_source: &git::DiffSource,
+) -> HashMap<PathBuf, String> {
+    match git::read_new_files(project_root, diff_source, |file| {
+        !is_ignored_path(file) && is_known_language(file)
+    }) {
+        Ok(Some(sources)) => return sources.into_iter().collect(),
+        Ok(None) => (),
+        Err(error) => {
+            eprintln!("⚠️ Could not read the analyzed version, using the working tree: {error}")
+        }
+    }
+    WalkDir::new(project_root)
+        .into_iter()
+        .filter_entry(|entry| !is_ignored(entry))
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_type().is_file())
+        .filter_map(|entry| {
+            let path = entry.path();
+            let relative_path = path
+                .strip_prefix(project_root)
+                .unwrap_or(path)
+                .to_path_buf();
+            if !is_known_language(&relative_path) {
+                return None;
+            }
+            let source = fs::read_to_string(path).ok()?;
+            Some((relative_path, source))
+        })
+        .collect()
+}
+
+/// Replaces a leading `Self` in the scope of a used symbol by the type or trait it designates.
+///
+/// ## Parameters:
+/// * `used_symbol` (`&mut Symbol`): Symbol used in a file,
+/// * `definitions` (`&[Definition]`): Symbols defined in the same file.
+fn resolve_self(used_symbol: &mut Symbol, definitions: &[Definition]) {
+    if used_symbol.scope.first().map(String::as_str) != Some("Self") {
+        return;
+    }
+    let Some(container) = definitions
+        .iter()
+        .filter(|definition| {
+            definition.symbol.kind.is_container() && definition.contains(used_symbol.line)
+        })
+        .min_by_key(|definition| definition.end_line - definition.start_line)
+    else {
+        return;
+    };
+    let mut self_scope = container.symbol.scope.clone();
+    if let Some(self_type) = self_scope
+        .last()
+        .and_then(|scope_name| symbol::split_trait_impl_scope(scope_name))
+        .map(|(self_type, _)| self_type.to_string())
+    {
+        self_scope.pop();
+        self_scope.push(self_type);
+    }
+    used_symbol.scope.splice(0..1, self_scope);
+}
+
+/// Scope of the module containing a line of a file: the scope of the innermost symbol defined
+/// around the line, without the symbols containing the line. Inline modules are not
+/// definitions, so the first enclosing scope that is not one is the module.
+///
+/// ## Parameters:
+/// * `definitions` (`&[Definition]`): Symbols defined in the file,
+/// * `line` (`usize`): Line number,
+/// * `file_scope` (`&[String]`): Scope of the file in its crate.
+///
+/// ## Returns:
+/// * (`Vec<String>`): Scope of the module containing the line.
+fn module_scope(definitions: &[Definition], line: usize, file_scope: &[String]) -> Vec<String> {
+    let Some(innermost) = innermost(definitions, line).first().copied() else {
+        return file_scope.to_vec();
+    };
+    let mut scope = innermost.scope.clone();
+    while definitions
+        .iter()
+        .any(|definition| definition.symbol.scope == scope && definition.contains(line))
+    {
+        scope.pop();
+    }
+    scope
+}
+
+/// Prefixes a relative path whose first segment is an item of the module it is used in (eg.
+/// `helper` or `Type::new`) with the scope of that module.
+///
+/// ## Parameters:
+/// * `used_symbol` (`&mut Symbol`): Symbol used in a file,
+/// * `definitions` (`&[Definition]`): Symbols defined in the same file,
+/// * `file_scope` (`&[String]`): Scope of the file in its crate.
+///
+/// ## Returns:
+/// * (`bool`): true iff the first segment is defined in the module of the path.
+fn resolve_item(
+    used_symbol: &mut Symbol,
+    definitions: &[Definition],
+    file_scope: &[String],
+) -> bool {
+    let Some(first) = used_symbol.scope.first() else {
+        return false;
+    };
+    let scope = module_scope(definitions, used_symbol.line, file_scope);
+    let is_defined = definitions.iter().any(|definition| {
+        !matches!(
+            definition.symbol.kind,
+            SymbolKind::Impl | SymbolKind::TraitImpl
+        ) && definition.symbol.scope.split_last() == Some((first, &scope))
+    });
+    if is_defined {
+        used_symbol.scope.splice(0..0, scope);
+    }
+    is_defined
+}
+
+/// Symbols whose definition contains a line, without the containers of other such symbols.
+///
+/// ## Parameters:
+/// * `definitions` (`&[Definition]`): Symbols defined in a file,
+/// * `line` (`usize`): Line number.
+///
+/// ## Returns:
+/// * (`Vec<&Symbol>`): Innermost symbols whose definition contains the line.
+fn innermost(definitions: &[Definition], line: usize) -> Vec<&Symbol> {
+    let enclosing: Vec<&Definition> = definitions
+        .iter()
+        .filter(|definition| definition.contains(line))
+        .collect();
+    enclosing
+        .iter()
+        .filter(|definition| {
+            !enclosing
+                .iter()
+                .any(|inner| definition.strictly_contains(inner))
+        })
+        .map(|definition| &definition.symbol)
+        .collect()
+}
+
+/// Extracts the definitions, imports and references of a source file.
+///
+/// ## Parameters:
+/// * `source` (`&str`): Content of the file,
+/// * `file` (`&std::path::PathBuf`): Path of the file, relative to the project root,
+/// * `language` (`&Languages`): Language of the file.
+///
+/// ## Returns:
+/// * (`Result<FileIndex>`): Index of the file, error if it could not be parsed.
+pub fn index_file(source: &str, file: &PathBuf, language: &Languages) -> Result<FileIndex> {
+    let tree = language.parse(source)?;
+    let root_node = tree.root_node();
+    let definitions = symbol::extract_definitions(&tree, file, source, language, true, |node| {
+        language.get_name_node_of_symbol(node, source)
+    })?;
+
+    let mut use_map = HashMap::new();
+    let mut globs = Vec::new();
+    if let Err(error) =
+        usage::extract_use_map(root_node, file, source, &mut use_map, &mut globs, language)
+    {
+        eprintln!("❌ File {file:?} gives error {error:?}");
+    }
+    for glob in &mut globs {
+        usage::resolve_with_use_map(&mut glob.scope, &use_map);
+    }
+
+    let file_scope = language.scope_from_path(file);
+    let locals = locals::local_bindings(root_node, source);
+    let references = match usage::extract_identifiers(root_node, file, source, language, &locals) {
+        Ok(used_symbols) => used_symbols
+            .into_iter()
+            .map(|mut used_symbol| {
+                // A member of a value of unknown type is only known by its name.
+                let is_member = matches!(used_symbol.kind, SymbolKind::Field | SymbolKind::Method)
+                    && used_symbol.scope.len() == 1;
+                // `macro_rules!` macros are not items of modules.
+                let is_path = !is_member
+                    && used_symbol.kind != SymbolKind::Macro
+                    && used_symbol.scope.first().is_some_and(|first| {
+                        !matches!(first.as_str(), "crate" | "self" | "super" | "Self")
+                            && !use_map.contains_key(first)
+                    });
+                // Explicit imports and items of the module take precedence over glob imports.
+                let is_unresolved =
+                    is_path && !resolve_item(&mut used_symbol, &definitions, &file_scope);
+                if !is_member {
+                    usage::resolve_with_use_map(&mut used_symbol.scope, &use_map);
+                    resolve_self(&mut used_symbol, &definitions);
+                }
+                Reference {
+                    symbol: used_symbol,
+                    is_unresolved,
+                }
+            })
+            .collect(),
+        Err(error) => {
+            eprintln!("❌ File {file:?} gives error {error:?}");
+            Vec::new()
+        }
+    };
+
+    Ok(FileIndex {
+        definitions,
+        use_map,
+        globs,
+        references,
+    })
+}
+
+impl SymbolIndex {
+    /// Indexes every source file of a project whose language is known, in the version the
+    /// analyzed changes lead to, so that lines match the changed symbols.
+    /// Files that can not be read or parsed are left out of the index.
+    /// With the cache, only files whose content changed since the previous run are parsed, and
+    /// the cache is updated afterwards.
+    ///
+    /// ## Parameters:
+    /// * `project_root` (`&std::path::Path`): Root of the project to index,
+    /// * `workspace` (`Rc<Workspace>`): Packages of the project,
+    /// * `sources` (`&HashMap<PathBuf, String>`): Source files of the analyzed version of the
+    ///   project, see `read_sources`,
+    /// * `use_cache` (`bool`): true to read and write the cache of the project.
+    ///
+    /// ## Returns:
+    /// * (`SymbolIndex`): Index of the project.
+    pub fn build(
+        project_root: &Path,
+        workspace: Rc<Workspace>,
+        sources: &HashMap<PathBuf, String>,
+        use_cache: bool,
+    ) -> Self {
+        let mut index = SymbolIndex {
+            workspace,
+            ..Default::default()
+        };
+        let previous = match use_cache {
+            true => Cache::load(project_root),
+            false => Cache::new(),
+        };
+        let mut cache = Cache::new();
+        for (relative_path, source) in sources {
+            let language = get_language_for_file(relative_path, &index.workspace);
+            if !use_cache {
+                if let Ok(file_index) = index_file(source, relative_path, &language) {
+                    index.files.insert(relative_path.clone(), file_index);
+                }
+                continue;
+            }
+            let Ok(hash) = cache::content_hash(source) else {
+                continue;
+            };
+            let scope = language.scope_from_path(relative_path);
+            let file_index = match previous.get(relative_path, &hash, &scope) {
+                Some(file_index) => file_index.clone(),
+                None => match index_file(source, relative_path, &language) {
+                    Ok(file_index) => file_index,
+                    Err(_) => continue,
+                },
+            };
+            cache.insert(relative_path.clone(), hash, scope, file_index.clone());
+            index.files.insert(relative_path.clone(), file_index);
+        }
+        if use_cache {
+            if let Err(error) = cache.save(project_root) {
+                eprintln!("⚠️ Could not write the cache: {error}");
+            }
+        }
+        index.reexports = Reexports::collect(&index.files, &index.workspace);
+        index
+    }
+
+    /// Gets the innermost symbols whose definition contains a given line of a file, eg. the
+    /// method rather than its implementation block.
+    ///
+    /// ## Parameters:
+    /// * `file` (`&std::path::Path`): Path of the file, relative to the project root,
+    /// * `line` (`usize`): Line number.
+    ///
+    /// ## Returns:
+    /// * (`Vec<&Symbol>`): Symbols enclosing the line, empty if the file is not indexed.
+    pub fn enclosing_symbols(&self, file: &Path, line: usize) -> Vec<&Symbol> {
+        self.files
+            .get(file)
+            .map(|file_index| innermost(&file_index.definitions, line))
+            .unwrap_or_default()
+    }
+
+    /// Iterates over the symbols defined in every indexed file.
+    ///
+    /// ## Returns:
+    /// * (`impl Iterator<Item = &Symbol>`): Defined symbols, in no particular order.
+    fn defined_symbols(&self) -> impl Iterator<Item = &Symbol> {
+        self.files.values().flat_map(|file_index| {
+            file_index
+                .definitions
+                .iter()
+                .map(|definition| &definition.symbol)
+        })
+    }
+
+    /// Returns true iff a symbol is a function defined in a trait or an implementation, that may
+    /// be called as a method.
+    ///
+    /// ## Parameters:
+    /// * `symbol` (`&Symbol`): Symbol to check.
+    ///
+    /// ## Returns:
+    /// * (`bool`): true iff the symbol is a function whose parent is a trait or an
+    ///   implementation.
+    pub fn is_method(&self, symbol: &Symbol) -> bool {
+        let Some((_, parent)) = symbol.scope.split_last() else {
+            return false;
+        };
+        symbol.kind == SymbolKind::Function
+            && self.files.get(&symbol.file).is_some_and(|file_index| {
+                file_index.definitions.iter().any(|definition| {
+                    matches!(
+                        definition.symbol.kind,
+                        SymbolKind::Trait | SymbolKind::Impl | SymbolKind::TraitImpl
+                    ) && definition.symbol.scope == parent
+                })
+            })
+    }
+
+    /// Returns true iff a scope is the scope of a trait defined in the project.
+    ///
+    /// ## Parameters:
+    /// * `scope` (`&[String]`): Scope to check.
+    ///
+    /// ## Returns:
+    /// * (`bool`): true iff a trait has this scope.
+    fn is_trait(&self, scope: &[String]) -> bool {
+        self.defined_symbols()
+            .any(|symbol| symbol.kind == SymbolKind::Trait && symbol.scope == scope)
+    }
+
+    /// Gets the implementations of an item of a trait: the items with the same name in every
+    /// implementation of the trait, and the implementations themselves, which inherit its
+    /// default body or value.
+    ///
+    /// ## Parameters:
+    /// * `item` (`&Symbol`): Method, associated constant or associated type declared in a trait.
+    ///
+    /// ## Returns:
+    /// * (`Vec<&Symbol>`): Implementations, empty if the symbol is not an item of a trait.
+    pub fn implementations_of(&self, item: &Symbol) -> Vec<&Symbol> {
+        let Some((name, trait_scope)) = item.scope.split_last() else {
+            return Vec::new();
+        };
+        let Some(trait_name) = trait_scope.last() else {
+            return Vec::new();
+        };
+        if !item.kind.is_associated() || !self.is_trait(trait_scope) {
+            return Vec::new();
+        }
+        let implements = |scope_name: &String| {
+            symbol::split_trait_impl_scope(scope_name)
+                .is_some_and(|(_, implemented)| implemented == trait_name)
+        };
+        self.defined_symbols()
+            .filter(|symbol| match symbol.kind {
+                SymbolKind::TraitImpl => symbol.scope.last().is_some_and(implements),
+                kind => {
+                    kind == item.kind
+                        && symbol.scope.last() == Some(name)
+                        && symbol.scope.iter().rev().nth(1).is_some_and(implements)
+                }
+            })
+            .collect()
+    }
+
+    /// Gets the items of traits implemented by an item of a trait implementation, used instead
+    /// of it through dynamic or static dispatch.
+    ///
+    /// ## Parameters:
+    /// * `item` (`&Symbol`): Method, associated constant or associated type of an
+    ///   implementation of a trait.
+    ///
+    /// ## Returns:
+    /// * (`Vec<&Symbol>`): Items declared in the trait, empty if the symbol is not an item of a
+    ///   trait implementation.
+    pub fn trait_items_of(&self, item: &Symbol) -> Vec<&Symbol> {
+        let Some((name, impl_scope)) = item.scope.split_last() else {
+            return Vec::new();
+        };
+        let Some((_, trait_name)) = impl_scope
+            .last()
+            .and_then(|scope_name| symbol::split_trait_impl_scope(scope_name))
+        else {
+            return Vec::new();
+        };
+        if !item.kind.is_associated() {
+            return Vec::new();
+        }
+        self.defined_symbols()
+            .filter(|symbol| {
+                symbol.kind == item.kind
+                    && symbol.scope.last() == Some(name)
+                    && symbol.scope.iter().rev().nth(1).map(String::as_str) == Some(trait_name)
+                    && self.is_trait(&symbol.scope[..symbol.scope.len() - 1])
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+impl SymbolIndex {
+    /// Indexes in-memory source files of a project without manifest.
+    ///
+    /// ## Parameters:
+    /// * `sources` (`&[(&str, &str)]`): Path relative to the project root and content of each
+    ///   file.
+    ///
+    /// ## Returns:
+    /// * (`SymbolIndex`): Index of the files.
+    pub(crate) fn from_sources(sources: &[(&str, &str)]) -> Self {
+        let mut index = SymbolIndex::default();
+        for (file, source) in sources {
+            let file = PathBuf::from(file);
+            let language = get_language_for_file(&file, &index.workspace);
+            let file_index = index_file(source, &file, &language).expect("source should parse");
+            index.files.insert(file, file_index);
+        }
+        index.reexports = Reexports::collect(&index.files, &index.workspace);
+        index
+    }
+
+    /// Gets the symbol defined with a scope, implementations aside.
+    ///
+    /// ## Parameters:
+    /// * `scope` (`&str`): Scope of the symbol, as `crate::module::name`.
+    ///
+    /// ## Returns:
+    /// * (`Symbol`): Defined symbol.
+    pub(crate) fn symbol(&self, scope: &str) -> Symbol {
+        self.defined_symbols()
+            .find(|symbol| {
+                !matches!(symbol.kind, SymbolKind::Impl | SymbolKind::TraitImpl)
+                    && symbol.scope.join("::") == scope
+            })
+            .cloned()
+            .unwrap_or_else(|| panic!("{scope} should be defined"))
+    }
+}
@@ -1,4 +1,4 @@
-use std::path::PathBuf;
+use std::{path::Path, rc::Rc};
 
 use anyhow::Result;
 use parsable_language::ParsableLanguage;
@@ -6,10 +6,10 @@ use rust::RustLanguage;
 use tree_sitter::{Node, Tree};
 use unknown::UnknownLanguage;
 
-use crate::symbol_kind::SymbolKind;
+use crate::{symbol_kind::SymbolKind, visibility::Visibility, workspace::Workspace};
 
 pub mod parsable_language;
-mod rust;
+pub(crate) mod rust;
 mod unknown;
 
 #[derive(Debug)]
@@ -22,10 +22,10 @@ pub enum Languages {
 }
 
 impl ParsableLanguage for Languages {
-    fn is_exported(&self, node: Node, source: &str) -> bool {
+    fn visibility(&self, node: Node, source: &str) -> Visibility {
         match &self {
-            Languages::Rust(language) => language.is_exported(node, source),
-            Languages::Unknown(language) => language.is_exported(node, source),
+            Languages::Rust(language) => language.visibility(node, source),
+            Languages::Unknown(language) => language.visibility(node, source),
         }
     }
 
@@ -46,14 +46,22 @@ impl ParsableLanguage for Languages {
     fn get_name_node_of_symbol<'a>(
         &self,
         node: &Node<'a>,
+        source: &str,
     ) -> Option<(Node<'a>, &'static SymbolKind)> {
         match &self {
-            Languages::Rust(language) => language.get_name_node_of_symbol(node),
-            Languages::Unknown(language) => language.get_name_node_of_symbol(node),
+            Languages::Rust(language) => language.get_name_node_of_symbol(node, source),
+            Languages::Unknown(language) => language.get_name_node_of_symbol(node, source),
         }
     }
 
-    fn scope_from_path(&self, file_path: &PathBuf) -> Vec<String> {
+    fn definition_start<'a>(&self, node: &Node<'a>) -> Node<'a> {
+        match &self {
+            Languages::Rust(language) => language.definition_start(node),
+            Languages::Unknown(language) => language.definition_start(node),
+        }
+    }
+
+    fn scope_from_path(&self, file_path: &Path) -> Vec<String> {
         match &self {
             Languages::Rust(language) => language.scope_from_path(file_path),
             Languages::Unknown(language) => language.scope_from_path(file_path),
@@ -61,18 +69,33 @@ impl ParsableLanguage for Languages {
     }
 }
 
+/// Returns true iff the language of a file is known, from its extension.
+///
+/// ## Parameters:
+/// * `file_name` (`&std::path::Path`): Name of the file.
+///
+/// ## Returns:
+/// * (`bool`): true iff the file is a source file of a supported language.
+pub fn is_known_language(file_name: &Path) -> bool {
+    !matches!(
+        get_language_for_file(file_name, &Rc::default()),
+        Languages::Unknown(_)
+    )
+}
+
 /// Returns the language used in a given file.
 /// Will use the file extension.
 ///
 /// ## Parameters:
-/// * `file_name` (`&std::path::PathBuf`): Name of the file to get the language from.
+/// * `file_name` (`&std::path::Path`): Name of the file to get the language from,
+/// * `workspace` (`&Rc<Workspace>`): Packages of the analyzed project.
 ///
 /// ## Returns:
 /// * (`Languages`): Language identified in the given file. If identification fails,
-/// `Languages::Unknown` will be returned.
-pub fn get_language_for_file(file_name: &PathBuf) -> Languages {
+///   `Languages::Unknown` will be returned.
+pub fn get_language_for_file(file_name: &Path, workspace: &Rc<Workspace>) -> Languages {
     match file_name.extension().and_then(|v| v.to_str()) {
-        Some("rs") => Languages::Rust(RustLanguage {}),
+        Some("rs") => Languages::Rust(RustLanguage::new(Rc::clone(workspace))),
         _ => Languages::Unknown(UnknownLanguage {}),
     }
 }
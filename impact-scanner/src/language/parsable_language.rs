@@ -1,21 +1,21 @@
-use std::path::PathBuf;
+use std::path::Path;
 
 use anyhow::Result;
 use tree_sitter::{Node, Tree};
 
-use crate::symbol_kind::SymbolKind;
+use crate::{symbol_kind::SymbolKind, visibility::Visibility};
 
 /// Trait for a supported language.
 pub trait ParsableLanguage {
-    /// Returns true iff the symbol will be made accessible from outside of the current scope.
+    /// Returns where the symbol defined by a node can be used from.
     ///
     /// ## Parameters:
     /// * `node` (`tree_sitter::Node`): File parsed by tree-sitter,
     /// * `source` (`&str`): Content of the current file.
     ///
     /// ## Returns:
-    /// * (`bool`): true iff the symbol is public.
-    fn is_exported(&self, node: Node, source: &str) -> bool;
+    /// * (`Visibility`): Visibility of the symbol.
+    fn visibility(&self, node: Node, source: &str) -> Visibility;
 
     /// Parse a file as a `tree_sitter::Tree`.
     ///
@@ -40,7 +40,8 @@ pub trait ParsableLanguage {
     /// program.
     ///
     /// ## Parameters:
-    /// * `node` (`tree_sitter::Node`): Node to get the name node of.
+    /// * `node` (`tree_sitter::Node`): Node to get the name node of,
+    /// * `source` (`&str`): Content of the file.
     ///
     /// ## Returns:
     /// * (`Option<(Node, &SymbolKind)>`): None if the symbol is not interesting, else node
@@ -48,14 +49,27 @@ pub trait ParsableLanguage {
     fn get_name_node_of_symbol<'a>(
         &self,
         node: &Node<'a>,
+        source: &str,
     ) -> Option<(Node<'a>, &'static SymbolKind)>;
 
-    /// Returns the scope to deduce from file name alone for the entirety of the file.
+    /// Returns the first node of the definition of a symbol, including what the language
+    /// attaches to it before its own node (eg. attributes).
+    ///
+    /// ## Parameters:
+    /// * `node` (`tree_sitter::Node`): Node defining the symbol.
+    ///
+    /// ## Returns:
+    /// * (`tree_sitter::Node`): First node of the definition, the given node if nothing precedes
+    ///   it.
+    fn definition_start<'a>(&self, node: &Node<'a>) -> Node<'a>;
+
+    /// Returns the scope of the entirety of the file, given by the module declarations leading
+    /// to it when known, else deduced from its name alone.
     ///
     /// ## Parameters:
-    /// * `file_path` (`&std::path::PathBuf`): Name of the file.
+    /// * `file_path` (`&std::path::Path`): Name of the file.
     ///
     /// ## Returns:
     /// * (`Vec<String>`): Scope of the given file constructed from the given path.
-    fn scope_from_path(&self, file_path: &PathBuf) -> Vec<String>;
+    fn scope_from_path(&self, file_path: &Path) -> Vec<String>;
 }
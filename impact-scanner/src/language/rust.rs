@@ -1,29 +1,157 @@
-use std::{
-    ffi::OsStr,
-    path::{Path, PathBuf},
-};
+use std::{ffi::OsStr, path::Path, rc::Rc};
 
-use crate::symbol_kind::SymbolKind;
+use crate::{
+    symbol::trait_impl_scope, symbol_kind::SymbolKind, visibility::Visibility, workspace::Workspace,
+};
 
 use super::parsable_language::ParsableLanguage;
 use anyhow::{anyhow, Result};
 use tree_sitter::{Node, Parser, Tree};
 use tree_sitter_rust::LANGUAGE as rust_language;
 
-#[derive(Debug)]
-pub struct RustLanguage {}
+#[derive(Default)]
+/// Rust, with the packages of the analyzed workspace to know which crate each file belongs to.
+pub struct RustLanguage {
+    /// Packages of the analyzed project.
+    workspace: Rc<Workspace>,
+}
+
+impl std::fmt::Debug for RustLanguage {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        formatter.write_str("RustLanguage")
+    }
+}
+
+impl RustLanguage {
+    /// Creates the language for the files of a workspace.
+    ///
+    /// ## Parameters:
+    /// * `workspace` (`Rc<Workspace>`): Packages of the analyzed project.
+    ///
+    /// ## Returns:
+    /// * (`RustLanguage`): Language computing scopes relative to the crates of the workspace.
+    pub fn new(workspace: Rc<Workspace>) -> Self {
+        RustLanguage { workspace }
+    }
+}
+
+/// Returns the content of the attributes applied to an item, eg. `test` for `#[test]`.
+///
+/// ## Parameters:
+/// * `node` (`&tree_sitter::Node`): Item to get the attributes of,
+/// * `source` (`&str`): Content of the file.
+///
+/// ## Returns:
+/// * (`Vec<&str>`): Content of every outer attribute preceding the item.
+pub(crate) fn attributes_of<'a>(node: &Node, source: &'a str) -> Vec<&'a str> {
+    let mut attributes = Vec::new();
+    let mut sibling = node.prev_sibling();
+    while let Some(current) = sibling {
+        match current.kind() {
+            "attribute_item" => {
+                if let Some(attribute) = current.named_child(0) {
+                    attributes.push(attribute.utf8_text(source.as_bytes()).unwrap_or(""));
+                }
+            }
+            "line_comment" | "block_comment" => (),
+            _ => break,
+        }
+        sibling = current.prev_sibling();
+    }
+    attributes
+}
+
+/// Name of a type, without its path, generic arguments nor reference.
+///
+/// ## Parameters:
+/// * `node` (`tree_sitter::Node`): Type node (eg. `fmt::Display`, `Foo<T>` or `&Foo`),
+/// * `source` (`&str`): Content of the file.
+///
+/// ## Returns:
+/// * (`Option<String>`): Name of the type (eg. `Display` or `Foo`).
+fn type_name(node: Node, source: &str) -> Option<String> {
+    match node.kind() {
+        "generic_type" | "reference_type" | "pointer_type" => {
+            type_name(node.child_by_field_name("type")?, source)
+        }
+        "scoped_type_identifier" | "scoped_identifier" => {
+            type_name(node.child_by_field_name("name")?, source)
+        }
+        _ => node
+            .utf8_text(source.as_bytes())
+            .ok()
+            .map(|s| s.to_string()),
+    }
+}
+
+/// Returns true iff the attribute marks a function run by a test harness.
+/// Any attribute path ending with `test` is accepted (`test`, `tokio::test`, ...).
+///
+/// ## Parameters:
+/// * `attribute` (`&str`): Content of the attribute.
+///
+/// ## Returns:
+/// * (`bool`): true iff the attribute marks a test function.
+fn is_test_attribute(attribute: &str) -> bool {
+    let path = attribute.split('(').next().unwrap_or("").trim();
+    path.rsplit("::").next() == Some("test")
+}
+
+/// Returns true iff the given function is a test function.
+///
+/// ## Parameters:
+/// * `node` (`&tree_sitter::Node`): `function_item` node,
+/// * `source` (`&str`): Content of the file.
+///
+/// ## Returns:
+/// * (`bool`): true iff the function has a test attribute.
+fn is_test_function(node: &Node, source: &str) -> bool {
+    attributes_of(node, source)
+        .into_iter()
+        .any(is_test_attribute)
+}
+
+/// Returns true iff the given item is declared in a trait or an implementation.
+///
+/// ## Parameters:
+/// * `node` (`&tree_sitter::Node`): Item node.
+///
+/// ## Returns:
+/// * (`bool`): true iff the item is in the body of a `trait_item` or an `impl_item`.
+fn is_associated_item(node: &Node) -> bool {
+    node.parent()
+        .filter(|list| list.kind() == "declaration_list")
+        .and_then(|list| list.parent())
+        .is_some_and(|container| matches!(container.kind(), "trait_item" | "impl_item"))
+}
 
 impl ParsableLanguage for RustLanguage {
-    fn is_exported(&self, node: Node, source: &str) -> bool {
-        let _test = 0;
-        for i in 0..node.child_count() {
-            let child = node.child(i).unwrap();
-            if child.kind() == "visibility_modifier" {
-                let text = child.utf8_text(source.as_bytes()).unwrap_or("");
-                return text.starts_with("pub");
+    fn visibility(&self, node: Node, source: &str) -> Visibility {
+        // Exported macros are at the root of the crate, the others can be used after their
+        // definition anywhere in it.
+        if node.kind() == "macro_definition" {
+            return match attributes_of(&node, source).contains(&"macro_export") {
+                true => Visibility::Public,
+                false => Visibility::Crate,
+            };
+        }
+        // Items of a trait or of a trait implementation are as visible as the trait, and variants
+        // of an enumeration as the enumeration.
+        if let Some(container) = node.parent().and_then(|list| list.parent()) {
+            match container.kind() {
+                "trait_item" | "enum_item" | "enum_variant" => {
+                    return self.visibility(container, source)
+                }
+                "impl_item" if container.child_by_field_name("trait").is_some() => {
+                    return Visibility::Public
+                }
+                _ => (),
             }
         }
-        false
+        node.children(&mut node.walk())
+            .find(|child| child.kind() == "visibility_modifier")
+            .map(|modifier| Visibility::parse(modifier.utf8_text(source.as_bytes()).unwrap_or("")))
+            .unwrap_or_default()
     }
 
     fn parse(&self, source: &str) -> Result<Tree> {
@@ -37,7 +165,20 @@ impl ParsableLanguage for RustLanguage {
 
     fn get_scope_name_for_node(&self, node: Node, source: &str) -> Option<String> {
         match node.kind() {
-            "mod_item" | "struct_item" | "enum_item" | "trait_item" | "function_item" => {
+            "mod_item"
+            | "struct_item"
+            | "enum_item"
+            | "union_item"
+            | "type_item"
+            | "trait_item"
+            | "function_item"
+            | "function_signature_item"
+            | "const_item"
+            | "static_item"
+            | "associated_type"
+            | "field_declaration"
+            | "enum_variant"
+            | "macro_definition" => {
                 if let Some(name_node) = node.child_by_field_name("name") {
                     return name_node
                         .utf8_text(source.as_bytes())
@@ -46,12 +187,14 @@ impl ParsableLanguage for RustLanguage {
                 }
             }
             "impl_item" => {
-                if let Some(name_node) = node.child_by_field_name("type") {
-                    return name_node
-                        .utf8_text(source.as_bytes())
-                        .ok()
-                        .map(|s| s.to_string());
-                }
+                let self_type = type_name(node.child_by_field_name("type")?, source)?;
+                return match node.child_by_field_name("trait") {
+                    Some(trait_node) => Some(trait_impl_scope(
+                        &self_type,
+                        &type_name(trait_node, source)?,
+                    )),
+                    None => Some(self_type),
+                };
             }
             _ => {}
         }
@@ -61,28 +204,93 @@ impl ParsableLanguage for RustLanguage {
     fn get_name_node_of_symbol<'a>(
         &self,
         node: &Node<'a>,
+        source: &str,
     ) -> Option<(Node<'a>, &'static SymbolKind)> {
-        if "function_item" == node.kind() {
-            if let Some(name_node) = node.child_by_field_name("name".to_string()) {
-                return Some((name_node, &SymbolKind::Function));
+        let kind = match node.kind() {
+            "function_item" if is_test_function(node, source) => &SymbolKind::Test,
+            "function_item" => &SymbolKind::Function,
+            "struct_item" => &SymbolKind::Struct,
+            "enum_item" => &SymbolKind::Enum,
+            "union_item" => &SymbolKind::Union,
+            "type_item" if is_associated_item(node) => &SymbolKind::AssocType,
+            "type_item" => &SymbolKind::TypeAlias,
+            "associated_type" => &SymbolKind::AssocType,
+            "const_item" if is_associated_item(node) => &SymbolKind::AssocConst,
+            "const_item" => &SymbolKind::Const,
+            "static_item" => &SymbolKind::Static,
+            "function_signature_item" => &SymbolKind::Function,
+            "trait_item" => &SymbolKind::Trait,
+            "impl_item" if node.child_by_field_name("trait").is_some() => &SymbolKind::TraitImpl,
+            "impl_item" => &SymbolKind::Impl,
+            "field_declaration" => &SymbolKind::Field,
+            "enum_variant" => &SymbolKind::Variant,
+            "macro_definition" => &SymbolKind::Macro,
+            _ => return None,
+        };
+        if node.kind() == "impl_item" {
+            return node
+                .child_by_field_name("type")
+                .map(|type_node| (type_node, kind));
+        }
+        node.child_by_field_name("name")
+            .map(|name_node| (name_node, kind))
+    }
+
+    fn definition_start<'a>(&self, node: &Node<'a>) -> Node<'a> {
+        // Outer attributes are siblings of the item, possibly separated by comments.
+        let mut start = *node;
+        let mut sibling = node.prev_sibling();
+        while let Some(current) = sibling {
+            match current.kind() {
+                "attribute_item" => start = current,
+                "line_comment" | "block_comment" => (),
+                _ => break,
             }
+            sibling = current.prev_sibling();
         }
-        None
+        start
     }
 
-    fn scope_from_path(&self, file_path: &PathBuf) -> Vec<String> {
-        let mut components = file_path
+    fn scope_from_path(&self, file_path: &Path) -> Vec<String> {
+        if let Some(module) = self.workspace.modules.module_of(file_path) {
+            return module.scope.clone();
+        }
+        // Files that no crate root reaches are guessed from their path: files of a known target
+        // are relative to the directory of its root file, where `mod` is the only transparent
+        // name. Other files fall back to the conventional layout.
+        let (relative_path, is_conventional) = match self.workspace.target_of(file_path) {
+            Some(target) if target.root == file_path => return vec!["crate".to_string()],
+            Some(target) => (
+                file_path
+                    .strip_prefix(target.module_dir())
+                    .unwrap_or(file_path),
+                false,
+            ),
+            None => (
+                self.workspace
+                    .package_of(file_path)
+                    .and_then(|package| file_path.strip_prefix(&package.dir).ok())
+                    .unwrap_or(file_path),
+                true,
+            ),
+        };
+        let transparent_stems: &[&str] = match is_conventional {
+            true => &["mod", "lib", "main"],
+            false => &["mod"],
+        };
+        let mut components = relative_path
             .components()
             .skip_while(|c| {
-                matches!(
-                    c.as_os_str().to_str(),
-                    Some("src") | Some("tests") | Some("src-bin") | Some(".")
-                )
+                is_conventional
+                    && matches!(
+                        c.as_os_str().to_str(),
+                        Some("src") | Some("tests") | Some("src-bin") | Some(".")
+                    )
             })
             .collect::<Vec<_>>();
-        if let Some(file_stem) = file_path.file_stem().and_then(|s| s.to_str()) {
+        if let Some(file_stem) = relative_path.file_stem().and_then(|s| s.to_str()) {
             components.pop();
-            if file_stem != "mod" && file_stem != "lib" && file_stem != "main" {
+            if !transparent_stems.contains(&file_stem) {
                 components.push(std::path::Component::Normal(OsStr::new(file_stem)));
             }
         }
@@ -1,9 +1,9 @@
-use std::path::PathBuf;
+use std::path::Path;
 
 use anyhow::{bail, Result};
 use tree_sitter::{Node, Tree};
 
-use crate::symbol_kind::SymbolKind;
+use crate::{symbol_kind::SymbolKind, visibility::Visibility};
 
 use super::parsable_language::ParsableLanguage;
 
@@ -11,8 +11,8 @@ use super::parsable_language::ParsableLanguage;
 pub struct UnknownLanguage {}
 
 impl ParsableLanguage for UnknownLanguage {
-    fn is_exported(&self, _node: Node, _source: &str) -> bool {
-        false
+    fn visibility(&self, _node: Node, _source: &str) -> Visibility {
+        Visibility::Private
     }
 
     fn parse(&self, _source: &str) -> Result<Tree> {
@@ -26,11 +26,16 @@ impl ParsableLanguage for UnknownLanguage {
     fn get_name_node_of_symbol<'a>(
         &self,
         _node: &Node<'a>,
+        _source: &str,
     ) -> Option<(Node<'a>, &'static SymbolKind)> {
         None
     }
 
-    fn scope_from_path(&self, _file_path: &PathBuf) -> Vec<String> {
+    fn definition_start<'a>(&self, node: &Node<'a>) -> Node<'a> {
+        *node
+    }
+
+    fn scope_from_path(&self, _file_path: &Path) -> Vec<String> {
         Vec::new()
     }
 }
@@ -0,0 +1,525 @@
+use std::collections::{HashMap, HashSet};
+
+use tree_sitter::Node;
+
+/// Names bound in the lexical scopes around the node being visited, innermost last, with the
+/// path of their type when it could be inferred.
+type Scopes = Vec<HashMap<String, Option<Vec<String>>>>;
+
+/// Traits bounding the type parameters of the function being visited, by name of the parameter.
+/// Parameters without bounds map to None.
+type Generics = HashMap<String, Option<Vec<String>>>;
+
+#[derive(Debug, Default)]
+/// Local bindings of a file, and types inferred for the values whose fields and methods are
+/// accessed.
+///
+/// ## Properties:
+/// * `identifiers` (`HashSet<usize>`): Position of the first byte of every identifier naming a
+///   local binding,
+/// * `receivers` (`HashMap<usize, Vec<String>>`): Path of the type of the value a field or a
+///   method is accessed on, as written in the file, by position of the first byte of the name of
+///   the field or method.
+pub struct Locals {
+    /// Position of the first byte of every identifier naming a local binding.
+    pub identifiers: HashSet<usize>,
+    /// Path of the type of the value a field or a method is accessed on, by position of its name.
+    pub receivers: HashMap<usize, Vec<String>>,
+}
+
+/// Finds the identifiers of a file that designate local bindings: `let` bindings, function and
+/// closure parameters, and bindings of `match` arms, `if let`, `while let` and `for` patterns,
+/// both where they are bound and where they are used.
+/// The type of bindings is inferred on a best-effort basis, from `self`, the type of parameters
+/// and `let` bindings, constructor calls (eg. `T::new()`) and struct expressions, to know the
+/// type of the values whose fields are accessed and methods called.
+///
+/// ## Parameters:
+/// * `root` (`tree_sitter::Node`): Root node of a parsed file,
+/// * `source` (`&str`): Content of the file.
+///
+/// ## Returns:
+/// * (`Locals`): Local bindings of the file, and receivers of fields and methods of known type.
+pub fn local_bindings(root: Node, source: &str) -> Locals {
+    let mut locals = Locals::default();
+    visit(root, source, &mut Vec::new(), &mut locals);
+    locals
+}
+
+/// Text of a node.
+///
+/// ## Parameters:
+/// * `node` (`tree_sitter::Node`): Node to get the text of,
+/// * `source` (`&str`): Content of the file.
+///
+/// ## Returns:
+/// * (`&str`): Text of the node, empty if it is not valid UTF-8.
+pub(crate) fn text<'a>(node: Node, source: &'a str) -> &'a str {
+    node.utf8_text(source.as_bytes()).unwrap_or("")
+}
+
+/// Gets the innermost binding of a name in the given scopes.
+///
+/// ## Parameters:
+/// * `scopes` (`&Scopes`): Names bound around the identifier,
+/// * `name` (`&str`): Name of the identifier.
+///
+/// ## Returns:
+/// * (`Option<&Option<Vec<String>>>`): Type of the binding if it is known, None if the name
+///   does not designate a local binding.
+fn binding<'a>(scopes: &'a Scopes, name: &str) -> Option<&'a Option<Vec<String>>> {
+    scopes.iter().rev().find_map(|scope| scope.get(name))
+}
+
+/// Returns true iff a name is bound in one of the given scopes.
+///
+/// ## Parameters:
+/// * `scopes` (`&Scopes`): Names bound around the identifier,
+/// * `name` (`&str`): Name of the identifier.
+///
+/// ## Returns:
+/// * (`bool`): true iff the name designates a local binding.
+fn is_bound(scopes: &Scopes, name: &str) -> bool {
+    binding(scopes, name).is_some()
+}
+
+/// Visits a node, binding the names introduced by its patterns and recording the identifiers
+/// designating local bindings and the receivers of fields and methods.
+///
+/// ## Parameters:
+/// * `node` (`tree_sitter::Node`): Node to visit, with its descendants,
+/// * `source` (`&str`): Content of the file,
+/// * `scopes` (`&mut Scopes`): Names bound around the node,
+/// * `locals` (`&mut Locals`): Local bindings found so far.
+fn visit(node: Node, source: &str, scopes: &mut Scopes, locals: &mut Locals) {
+    match node.kind() {
+        "identifier" => {
+            if is_bound(scopes, text(node, source)) {
+                locals.identifiers.insert(node.start_byte());
+            }
+        }
+        // Paths and macro definitions never name local bindings.
+        "scoped_identifier" | "scoped_type_identifier" | "macro_definition" => (),
+        "function_item" => {
+            // Nested functions do not capture the bindings around them.
+            let mut function_scopes = vec![HashMap::new()];
+            let generics = generics_of(node, source);
+            if let Some(parameters) = node.child_by_field_name("parameters") {
+                bind_parameters(parameters, source, &generics, &mut function_scopes, locals);
+            }
+            if let Some(body) = node.child_by_field_name("body") {
+                visit(body, source, &mut function_scopes, locals);
+            }
+        }
+        "closure_expression" => {
+            scopes.push(HashMap::new());
+            if let Some(parameters) = node.child_by_field_name("parameters") {
+                bind_parameters(parameters, source, &Generics::new(), scopes, locals);
+            }
+            if let Some(body) = node.child_by_field_name("body") {
+                visit(body, source, scopes, locals);
+            }
+            scopes.pop();
+        }
+        "let_declaration" => {
+            // The value is evaluated before the pattern binds its names.
+            for field in ["type", "value", "alternative"] {
+                if let Some(child) = node.child_by_field_name(field) {
+                    visit(child, source, scopes, locals);
+                }
+            }
+            let type_path = match node.child_by_field_name("type") {
+                Some(type_node) => type_path(type_node, source, &Generics::new()),
+                None => node
+                    .child_by_field_name("value")
+                    .and_then(|value| infer(value, source, scopes)),
+            };
+            if let Some(pattern) = node.child_by_field_name("pattern") {
+                bind_pattern(pattern, source, type_path, scopes, locals);
+            }
+        }
+        "let_condition" => {
+            // Names are bound in the scope pushed by the enclosing `if` or `while` for its
+            // consequence or body.
+            if let Some(value) = node.child_by_field_name("value") {
+                visit(value, source, scopes, locals);
+            }
+            if let Some(pattern) = node.child_by_field_name("pattern") {
+                bind_pattern(pattern, source, None, scopes, locals);
+            }
+        }
+        "for_expression" => {
+            if let Some(value) = node.child_by_field_name("value") {
+                visit(value, source, scopes, locals);
+            }
+            scopes.push(HashMap::new());
+            if let Some(pattern) = node.child_by_field_name("pattern") {
+                bind_pattern(pattern, source, None, scopes, locals);
+            }
+            if let Some(body) = node.child_by_field_name("body") {
+                visit(body, source, scopes, locals);
+            }
+            scopes.pop();
+        }
+        "match_arm" => {
+            scopes.push(HashMap::new());
+            if let Some(pattern) = node.child_by_field_name("pattern") {
+                bind_pattern(pattern, source, None, scopes, locals);
+            }
+            if let Some(value) = node.child_by_field_name("value") {
+                visit(value, source, scopes, locals);
+            }
+            scopes.pop();
+        }
+        "field_expression" => {
+            let receiver = node
+                .child_by_field_name("value")
+                .and_then(|value| infer(value, source, scopes));
+            if let Some((member, receiver)) = node.child_by_field_name("field").zip(receiver) {
+                locals.receivers.insert(member.start_byte(), receiver);
+            }
+            visit_children(node, source, scopes, locals);
+        }
+        "token_tree" => visit_token_tree(node, source, scopes, locals),
+        "if_expression" | "while_expression" => {
+            // Names bound by the condition are only in scope in the consequence or the body,
+            // not in the `else` branch.
+            scopes.push(HashMap::new());
+            for field in ["condition", "consequence", "body"] {
+                if let Some(child) = node.child_by_field_name(field) {
+                    visit(child, source, scopes, locals);
+                }
+            }
+            scopes.pop();
+            if let Some(alternative) = node.child_by_field_name("alternative") {
+                visit(alternative, source, scopes, locals);
+            }
+        }
+        "block" => {
+            scopes.push(HashMap::new());
+            visit_children(node, source, scopes, locals);
+            scopes.pop();
+        }
+        _ => visit_children(node, source, scopes, locals),
+    }
+}
+
+/// Visits the named children of a node in order.
+///
+/// ## Parameters:
+/// * `node` (`tree_sitter::Node`): Node whose children to visit,
+/// * `source` (`&str`): Content of the file,
+/// * `scopes` (`&mut Scopes`): Names bound around the node,
+/// * `locals` (`&mut Locals`): Local bindings found so far.
+fn visit_children(node: Node, source: &str, scopes: &mut Scopes, locals: &mut Locals) {
+    for child in node.named_children(&mut node.walk()) {
+        visit(child, source, scopes, locals);
+    }
+}
+
+/// Records the identifiers of a token tree designating local bindings. Only identifiers that
+/// are not part of a path, nor fields, can be. Fields and methods accessed on a local binding or
+/// on `self` (eg. `x.field` or `x.method(...)`) get the type of the binding as receiver.
+///
+/// ## Parameters:
+/// * `node` (`tree_sitter::Node`): `token_tree` node,
+/// * `source` (`&str`): Content of the file,
+/// * `scopes` (`&mut Scopes`): Names bound around the node,
+/// * `locals` (`&mut Locals`): Local bindings found so far.
+fn visit_token_tree(node: Node, source: &str, scopes: &mut Scopes, locals: &mut Locals) {
+    for child in node.children(&mut node.walk()) {
+        match child.kind() {
+            "token_tree" => visit_token_tree(child, source, scopes, locals),
+            "identifier" => {
+                let is_segment = child
+                    .prev_sibling()
+                    .is_some_and(|previous| matches!(previous.kind(), "::" | "."))
+                    || child
+                        .next_sibling()
+                        .is_some_and(|next| matches!(next.kind(), "::" | "!"));
+                if !is_segment && is_bound(scopes, text(child, source)) {
+                    locals.identifiers.insert(child.start_byte());
+                }
+            }
+            _ => (),
+        }
+        let member = child
+            .next_sibling()
+            .filter(|dot| dot.kind() == ".")
+            .and_then(|dot| dot.next_sibling())
+            .filter(|name| name.kind() == "identifier");
+        let is_path_segment = child
+            .prev_sibling()
+            .is_some_and(|previous| matches!(previous.kind(), "::" | "."));
+        let receiver = match child.kind() {
+            _ if is_path_segment => None,
+            "self" => Some(vec!["Self".to_string()]),
+            "identifier" => binding(scopes, text(child, source)).cloned().flatten(),
+            _ => None,
+        };
+        if let Some((member, receiver)) = member.zip(receiver) {
+            locals.receivers.insert(member.start_byte(), receiver);
+        }
+    }
+}
+
+/// Binds the parameters of a function or closure in the innermost scope, with their type.
+///
+/// ## Parameters:
+/// * `parameters` (`tree_sitter::Node`): `parameters` or `closure_parameters` node,
+/// * `source` (`&str`): Content of the file,
+/// * `generics` (`&Generics`): Type parameters of the function,
+/// * `scopes` (`&mut Scopes`): Names bound around the parameters,
+/// * `locals` (`&mut Locals`): Local bindings found so far.
+fn bind_parameters(
+    parameters: Node,
+    source: &str,
+    generics: &Generics,
+    scopes: &mut Scopes,
+    locals: &mut Locals,
+) {
+    for parameter in parameters.named_children(&mut parameters.walk()) {
+        match parameter.kind() {
+            "parameter" => {
+                let type_path = parameter
+                    .child_by_field_name("type")
+                    .and_then(|type_node| type_path(type_node, source, generics));
+                if let Some(pattern) = parameter.child_by_field_name("pattern") {
+                    bind_pattern(pattern, source, type_path, scopes, locals);
+                }
+            }
+            "self_parameter" | "variadic_parameter" | "attribute_item" => (),
+            _ => bind_pattern(parameter, source, None, scopes, locals),
+        }
+    }
+}
+
+/// Binds the names introduced by a pattern in the innermost scope.
+/// Identifiers starting with an uppercase letter are taken as constants or unit variants
+/// rather than bindings, as the naming conventions of Rust suggest.
+///
+/// ## Parameters:
+/// * `pattern` (`tree_sitter::Node`): Pattern node,
+/// * `source` (`&str`): Content of the file,
+/// * `type_path` (`Option<Vec<String>>`): Type of the value matched by the pattern, if known,
+/// * `scopes` (`&mut Scopes`): Names bound around the pattern,
+/// * `locals` (`&mut Locals`): Local bindings found so far.
+fn bind_pattern(
+    pattern: Node,
+    source: &str,
+    type_path: Option<Vec<String>>,
+    scopes: &mut Scopes,
+    locals: &mut Locals,
+) {
+    match pattern.kind() {
+        "identifier" | "shorthand_field_identifier" => {
+            let name = text(pattern, source);
+            if !name.starts_with(|c: char| c.is_uppercase()) {
+                locals.identifiers.insert(pattern.start_byte());
+                if let Some(scope) = scopes.last_mut() {
+                    scope.insert(name.to_string(), type_path);
+                }
+            }
+        }
+        "scoped_identifier" | "scoped_type_identifier" | "type_identifier" => (),
+        // `mut x` and `ref x` bind a value of the matched type.
+        "mut_pattern" | "ref_pattern" => {
+            for child in pattern.named_children(&mut pattern.walk()) {
+                bind_pattern(child, source, type_path.clone(), scopes, locals);
+            }
+        }
+        "tuple_struct_pattern" | "struct_pattern" => {
+            let type_node = pattern.child_by_field_name("type");
+            for child in pattern.named_children(&mut pattern.walk()) {
+                if Some(child) != type_node {
+                    bind_pattern(child, source, None, scopes, locals);
+                }
+            }
+        }
+        "field_pattern" => match pattern.child_by_field_name("pattern") {
+            Some(inner) => bind_pattern(inner, source, None, scopes, locals),
+            None => {
+                if let Some(name) = pattern.child_by_field_name("name") {
+                    bind_pattern(name, source, None, scopes, locals);
+                }
+            }
+        },
+        "match_pattern" => {
+            let condition = pattern.child_by_field_name("condition");
+            for child in pattern.named_children(&mut pattern.walk()) {
+                if Some(child) != condition {
+                    bind_pattern(child, source, None, scopes, locals);
+                }
+            }
+            // The guard can use the names bound by the pattern.
+            if let Some(condition) = condition {
+                visit(condition, source, scopes, locals);
+            }
+        }
+        _ => {
+            for child in pattern.named_children(&mut pattern.walk()) {
+                bind_pattern(child, source, None, scopes, locals);
+            }
+        }
+    }
+}
+
+/// Gets the traits bounding the type parameters of a function, in its type parameters or its
+/// `where` clause. Only the first bound of each parameter is kept.
+///
+/// ## Parameters:
+/// * `function` (`tree_sitter::Node`): `function_item` node,
+/// * `source` (`&str`): Content of the file.
+///
+/// ## Returns:
+/// * (`Generics`): Trait bounding each type parameter, by name of the parameter.
+fn generics_of(function: Node, source: &str) -> Generics {
+    let mut generics = Generics::new();
+    let first_bound = |bounds: Option<Node>| {
+        bounds
+            .and_then(|bounds| bounds.named_child(0))
+            .and_then(|bound| type_path(bound, source, &Generics::new()))
+    };
+    if let Some(parameters) = function.child_by_field_name("type_parameters") {
+        for parameter in parameters.named_children(&mut parameters.walk()) {
+            if let Some(name) = parameter.child_by_field_name("name") {
+                let bound = first_bound(parameter.child_by_field_name("bounds"));
+                generics.insert(text(name, source).to_string(), bound);
+            }
+        }
+    }
+    let Some(where_clause) = function
+        .named_children(&mut function.walk())
+        .find(|child| child.kind() == "where_clause")
+    else {
+        return generics;
+    };
+    for predicate in where_clause.named_children(&mut where_clause.walk()) {
+        let Some(name) = predicate.child_by_field_name("left") else {
+            continue;
+        };
+        let bound = first_bound(predicate.child_by_field_name("bounds"));
+        if let Some(existing) = generics.get_mut(text(name, source)) {
+            *existing = existing.take().or(bound);
+        }
+    }
+    generics
+}
+
+/// Gets the path of the type whose methods can be called on a value of the given type.
+/// References and smart pointers are looked through, generic arguments are left out, and
+/// trait objects, `impl Trait` and bounded type parameters are given the path of their trait.
+///
+/// ## Parameters:
+/// * `type_node` (`tree_sitter::Node`): Type node,
+/// * `source` (`&str`): Content of the file,
+/// * `generics` (`&Generics`): Type parameters in scope.
+///
+/// ## Returns:
+/// * (`Option<Vec<String>>`): Path of the type as written, None if it can not be known.
+fn type_path(type_node: Node, source: &str, generics: &Generics) -> Option<Vec<String>> {
+    match type_node.kind() {
+        "type_identifier" => match generics.get(text(type_node, source)) {
+            Some(bound) => bound.clone(),
+            None => Some(vec![text(type_node, source).to_string()]),
+        },
+        "scoped_type_identifier" => path_of(type_node, source),
+        "reference_type" | "dynamic_type" | "abstract_type" => {
+            let field = match type_node.kind() {
+                "reference_type" => "type",
+                _ => "trait",
+            };
+            type_path(type_node.child_by_field_name(field)?, source, generics)
+        }
+        "generic_type" => {
+            let base = type_path(type_node.child_by_field_name("type")?, source, generics)?;
+            match base.last().map(String::as_str) {
+                Some("Box" | "Rc" | "Arc") => type_path(
+                    type_node
+                        .child_by_field_name("type_arguments")?
+                        .named_child(0)?,
+                    source,
+                    generics,
+                ),
+                _ => Some(base),
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Gets the segments of a path without generic arguments (eg. `a::B`).
+///
+/// ## Parameters:
+/// * `node` (`tree_sitter::Node`): Path node,
+/// * `source` (`&str`): Content of the file.
+///
+/// ## Returns:
+/// * (`Option<Vec<String>>`): Segments of the path, None if it has generic arguments.
+fn path_of(node: Node, source: &str) -> Option<Vec<String>> {
+    let path: String = text(node, source).split_whitespace().collect();
+    if path.is_empty() || path.contains(['<', '>']) {
+        return None;
+    }
+    Some(path.split("::").map(str::to_string).collect())
+}
+
+/// Returns true iff an associated function is assumed to build a value of its type, given its
+/// name (eg. `new`, `default` or `with_capacity`).
+///
+/// ## Parameters:
+/// * `name` (`&str`): Name of the function.
+///
+/// ## Returns:
+/// * (`bool`): true iff the function is conventionally a constructor.
+fn is_constructor(name: &str) -> bool {
+    matches!(name, "new" | "default" | "from")
+        || name.starts_with("new_")
+        || name.starts_with("with_")
+}
+
+/// Infers the type of an expression, on a best-effort basis.
+///
+/// ## Parameters:
+/// * `expression` (`tree_sitter::Node`): Expression node,
+/// * `source` (`&str`): Content of the file,
+/// * `scopes` (`&Scopes`): Names bound around the expression.
+///
+/// ## Returns:
+/// * (`Option<Vec<String>>`): Path of the type as written, None if it can not be inferred.
+fn infer(expression: Node, source: &str, scopes: &Scopes) -> Option<Vec<String>> {
+    match expression.kind() {
+        "self" => Some(vec!["Self".to_string()]),
+        "identifier" => binding(scopes, text(expression, source))?.clone(),
+        "reference_expression" => infer(expression.child_by_field_name("value")?, source, scopes),
+        "parenthesized_expression" => infer(expression.named_child(0)?, source, scopes),
+        "struct_expression" => {
+            let name = expression.child_by_field_name("name")?;
+            match name.kind() {
+                "type_identifier" => Some(vec![text(name, source).to_string()]),
+                "scoped_type_identifier" => path_of(name, source),
+                _ => type_path(name, source, &Generics::new()),
+            }
+        }
+        "call_expression" => {
+            let function = expression
+                .child_by_field_name("function")
+                .filter(|function| function.kind() == "scoped_identifier")?;
+            let name = function.child_by_field_name("name")?;
+            if !is_constructor(text(name, source)) {
+                return None;
+            }
+            let type_node = function.child_by_field_name("path")?;
+            let path = match type_node.kind() {
+                "generic_type" => type_path(type_node, source, &Generics::new()),
+                _ => path_of(type_node, source),
+            }?;
+            // Constructors are associated to types, named in upper camel case.
+            path.last()
+                .filter(|last| last.starts_with(|c: char| c.is_uppercase()))
+                .is_some()
+                .then_some(path)
+        }
+        _ => None,
+    }
+}
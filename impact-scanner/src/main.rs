@@ -1,96 +1,404 @@
 pub use std::collections::HashSet;
-use std::path::PathBuf;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
 
 use crate::language::parsable_language::ParsableLanguage;
-use anyhow::Result;
-use clap::Parser;
+use anyhow::{anyhow, Result};
+use clap::{Parser, Subcommand};
 use language::get_language_for_file;
 
+mod cache;
 mod git;
+mod impact;
+mod index;
 mod language;
+mod locals;
+mod module_tree;
+mod reexport;
+mod report;
+mod runner;
 mod symbol;
 mod symbol_kind;
+mod symbol_status;
+mod test_selection;
 mod usage;
+mod visibility;
+mod workspace;
 
 #[derive(Parser, Debug)]
 #[command(name = "impact-scanner")]
 #[command(about = "Analyze what code is affected by your staged changes", long_about = None)]
+#[command(group(clap::ArgGroup::new("diff_source").multiple(false)))]
 /// Arguments received by the main command.
 ///
 /// ## Arguments:
 /// - `debug` (`bool`): true to display more info, defaults to false,
-/// - `path` (`String`): Path to the project to analyze, defaults to current directory.
+/// - `path` (`String`): Path inside the repository to analyze, defaults to current directory.
+///   Tests are run from this directory,
+/// - `usage` (`bool`): true to display where changed symbols are used, defaults to false,
+/// - `impact` (`bool`): true to display affected symbols, defaults to false,
+/// - `tests` (`bool`): true to display affected tests, defaults to false,
+/// - `unreachable` (`bool`): true to display the Rust files no crate root reaches, defaults to
+///   false,
+/// - `staged` (`bool`): analyze staged changes, the default,
+/// - `worktree` (`bool`): analyze changes of the working tree not staged yet,
+/// - `base` (`Option<String>`): analyze changes of HEAD since it diverged from this revision,
+/// - `range` (`Option<String>`): analyze changes of a revision range (`a..b` or `a...b`),
+/// - `commit` (`Option<String>`): analyze changes introduced by this commit,
+/// - `format` (`report::OutputFormat`): format of the results, defaults to text,
+/// - `no_cache` (`bool`): true to parse every file instead of using the cache, defaults to false,
+/// - `strict_methods` (`bool`): true to ignore method calls on values of unknown type instead of
+///   matching them by name, defaults to false,
+/// - `command` (`Option<Command>`): Action to perform after the analysis, if any.
 struct Args {
     #[arg(short, long)]
     /// Display more information.
     debug: bool,
     #[arg(short, long, default_value_t = String::from("."))]
-    /// Path to the project to analyze.
+    /// Path inside the repository to analyze, the whole repository is analyzed.
     path: String,
     #[arg(short, long)]
     /// Show usage of symbols
     usage: bool,
+    #[arg(short, long)]
+    /// Show symbols transitively affected by the changes.
+    impact: bool,
+    #[arg(short, long)]
+    /// Show the tests affected by the changes, as `cargo test` filters.
+    tests: bool,
+    #[arg(long)]
+    /// Show the Rust files that no crate root reaches, and that the compiler ignores.
+    unreachable: bool,
+    #[arg(long, group = "diff_source")]
+    /// Analyze changes staged in the index (default).
+    staged: bool,
+    #[arg(long, group = "diff_source")]
+    /// Analyze changes of the working tree not staged yet.
+    worktree: bool,
+    #[arg(long, group = "diff_source", value_name = "REV")]
+    /// Analyze changes of HEAD since it diverged from the given revision.
+    base: Option<String>,
+    #[arg(long, group = "diff_source", value_name = "A..B")]
+    /// Analyze changes between two revisions (`a..b`, or `a...b` from their merge base).
+    range: Option<String>,
+    #[arg(long, group = "diff_source", value_name = "REV")]
+    /// Analyze changes introduced by a single commit.
+    commit: Option<String>,
+    #[arg(short, long, value_enum, default_value_t = report::OutputFormat::Text)]
+    /// Format of the results.
+    format: report::OutputFormat,
+    #[arg(long)]
+    /// Parse every file of the project, without reading nor writing the cache.
+    no_cache: bool,
+    #[arg(long)]
+    /// Ignore method calls on values whose type can not be inferred, instead of matching them
+    /// with every method of the same name.
+    strict_methods: bool,
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+/// Actions to perform after the analysis.
+enum Command {
+    /// Run the tests affected by the changes.
+    /// The whole test suite is run when the analysis is inconclusive.
+    Run {
+        #[arg(long, value_enum, default_value_t = runner::TestRunner::Cargo)]
+        /// Tool used to run the tests.
+        runner: runner::TestRunner,
+        #[arg(long, value_name = "PATH")]
+        /// Write a JUnit XML report of the selected tests and their outcomes.
+        junit: Option<PathBuf>,
+    },
+    /// Manage the cache of parsed files, stored in `target/impact-scanner/`.
+    Cache {
+        #[command(subcommand)]
+        action: CacheAction,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+/// Actions on the cache of parsed files.
+enum CacheAction {
+    /// Remove the cache.
+    Clear,
+}
+
+impl Args {
+    /// Changes to analyze, according to the diff source flags.
+    ///
+    /// ## Returns:
+    /// * (`git::DiffSource`): Source of the changes, staged changes by default.
+    fn diff_source(&self) -> git::DiffSource {
+        if self.worktree {
+            git::DiffSource::Worktree
+        } else if let Some(base) = &self.base {
+            git::DiffSource::Base(base.clone())
+        } else if let Some(range) = &self.range {
+            git::DiffSource::Range(range.clone())
+        } else if let Some(commit) = &self.commit {
+            git::DiffSource::Commit(commit.clone())
+        } else {
+            git::DiffSource::Staged
+        }
+    }
 }
 
 /// Get changed symbols in the given file.
 ///
 /// ## Parameters:
+/// * `project_root` (`&std::path::Path`): Root of the working tree of the git repository,
+/// * `diff_source` (`&git::DiffSource`): Changes being analyzed,
+/// * `sources` (`&HashMap<PathBuf, String>`): Source files after the changes,
 /// * `file` (`&std::path::PathBuf`): Name of the file,
 /// * `language` (`&language::Languages`): Language of the file,
-/// * `changed_lines` (`&Vec<usize>`): List of lines with staged changes in the file,
+/// * `changes` (`&git::FileChanges`): Lines changed in the file,
 /// * `debug` (`bool`): true iff more information should be displayed.
 ///
 /// ## Returns:
 /// * (`Result<Vec<symbol::Symbol>>`): List of symbols that changed in the given file.
 fn symbols_from_changes(
+    project_root: &Path,
+    diff_source: &git::DiffSource,
+    sources: &HashMap<PathBuf, String>,
     file: &PathBuf,
     language: &language::Languages,
-    changed_lines: &Vec<usize>,
+    changes: &git::FileChanges,
     debug: bool,
 ) -> Result<Vec<symbol::Symbol>> {
     if debug {
-        println!("Processing {:?}", file);
-        println!("Language is {:?}", language);
+        eprintln!("Processing {:?}", file);
+        eprintln!("Language is {:?}", language);
     }
-    let source = std::fs::read_to_string(&file)?;
-    let tree = language.parse(&source)?;
+    let new_source = match changes.is_deleted {
+        true => None,
+        false => Some(
+            sources
+                .get(file)
+                .ok_or_else(|| anyhow!("file could not be read"))?,
+        ),
+    };
+    let new_tree = new_source.as_ref().map(|s| language.parse(s)).transpose()?;
+    let old_file = changes.old_path.clone().unwrap_or(file.clone());
+    let old_source = match changes.old_path {
+        Some(_) => git::read_old_file(project_root, diff_source, &old_file)?,
+        None => None,
+    };
+    let old_tree = old_source.as_ref().map(|s| language.parse(s)).transpose()?;
 
-    let changed_lines: HashSet<usize> = changed_lines.iter().copied().collect();
-    symbol::extract_changed_symbols(&tree, file, &source, &changed_lines, &language)
+    let added_lines: HashSet<usize> = changes.added_lines.iter().copied().collect();
+    let removed_lines: HashSet<usize> = changes.removed_lines.iter().copied().collect();
+    let new_version = match (&new_source, &new_tree) {
+        (Some(source), Some(tree)) => Some(symbol::FileVersion {
+            file,
+            source,
+            tree,
+            changed_lines: &added_lines,
+        }),
+        _ => None,
+    };
+    let old_version = match (&old_source, &old_tree) {
+        (Some(source), Some(tree)) => Some(symbol::FileVersion {
+            file: &old_file,
+            source,
+            tree,
+            changed_lines: &removed_lines,
+        }),
+        _ => None,
+    };
+    symbol::extract_symbols_changed_between(old_version.as_ref(), new_version.as_ref(), language)
 }
 
 /// Runs the main impact-scanner command with the arguments from `Args`.
-/// - Get staged changes,
+/// - Get changes from the selected diff source,
 /// - Deduce changed symbols,
-/// - Display them.
+/// - Optionally deduce affected symbols and tests,
+/// - Display them in the requested format,
+/// - Optionally run the affected tests.
 ///
 /// ## Returns:
 /// - (`Result<()>`): Ok if no critical error, else description of the error.
 fn main() -> Result<()> {
     let args = Args::parse();
-    let changed_map = git::get_changed_lines(&PathBuf::from(&args.path))?;
+    let project_root = git::discover_root(Path::new(&args.path))?;
+    if args.debug {
+        eprintln!("Project root: {:?}", project_root);
+    }
+    if let Some(Command::Cache {
+        action: CacheAction::Clear,
+    }) = args.command
+    {
+        match cache::clear(&project_root)? {
+            true => println!("🧹 Cache cleared"),
+            false => println!("Cache is already empty"),
+        }
+        return Ok(());
+    }
+    let diff_source = args.diff_source();
+    let changed_map = git::get_changed_lines(&project_root, &diff_source)?;
+    let mut workspace = workspace::Workspace::load(&project_root, &diff_source);
+    let sources = index::read_sources(&project_root, &diff_source);
+    workspace.modules = module_tree::ModuleTree::build(
+        &project_root,
+        &workspace.packages,
+        &sources,
+        !args.no_cache,
+    );
+    let workspace = Rc::new(workspace);
+    if args.debug {
+        eprintln!("Workspace: {:?}", workspace);
+    }
     if args.debug {
-        println!("Changed lines: {:?}", changed_map);
+        eprintln!("Changed lines: {:?}", changed_map);
     }
 
-    for file in changed_map.keys() {
-        let language: language::Languages = get_language_for_file(file);
-        match symbols_from_changes(file, &language, &changed_map[file], args.debug) {
+    let mut report = report::Report {
+        unreachable: args
+            .unreachable
+            .then(|| workspace.modules.unreachable(&workspace.packages, &sources)),
+        ..Default::default()
+    };
+    let mut all_changed_symbols = Vec::new();
+    let mut files: Vec<&PathBuf> = changed_map.keys().collect();
+    files.sort();
+    for file in files {
+        let language: language::Languages = get_language_for_file(file, &workspace);
+        if let language::Languages::Unknown(_) = language {
+            report.files.push(report::FileReport {
+                file: file.clone(),
+                symbols: Vec::new(),
+                error: None,
+                skipped: true,
+            });
+            continue;
+        }
+        match symbols_from_changes(
+            &project_root,
+            &diff_source,
+            &sources,
+            file,
+            &language,
+            &changed_map[file],
+            args.debug,
+        ) {
             Ok(changed_symbols) => {
-                println!("✏️ Changed symbols in {file:?}:");
-                for symbol in changed_symbols {
-                    println!("   - {symbol},");
-                    if args.usage {
-                        let usage = usage::find_symbol_usages(
-                            &PathBuf::from(&args.path),
-                            &symbol,
-                            &language,
-                        );
-                        eprintln!("DEBUGPRINT[30]: main.rs:79: usage={:#?}", usage);
-                    }
+                all_changed_symbols.extend(changed_symbols.iter().cloned());
+                report.files.push(report::FileReport {
+                    file: file.clone(),
+                    symbols: changed_symbols,
+                    error: None,
+                    skipped: false,
+                });
+            }
+            Err(error) => {
+                report.conclusive = false;
+                report.files.push(report::FileReport {
+                    file: file.clone(),
+                    symbols: Vec::new(),
+                    error: Some(error.to_string()),
+                    skipped: false,
+                });
+            }
+        }
+    }
+
+    let needs_impact = args.impact || args.tests || args.command.is_some();
+    let index = match args.usage || needs_impact {
+        true => index::SymbolIndex::build(&project_root, workspace, &sources, !args.no_cache),
+        false => index::SymbolIndex {
+            workspace,
+            ..Default::default()
+        },
+    };
+    if args.debug {
+        eprintln!("Indexed {} files", index.files.len());
+    }
+
+    if args.usage {
+        let mut all_usages = Vec::new();
+        for symbol in &all_changed_symbols {
+            let language = get_language_for_file(&symbol.file, &index.workspace);
+            let mut usages: Vec<usage::Usage> =
+                usage::find_symbol_usages(&index, symbol, &language, args.strict_methods)
+                    .into_iter()
+                    .collect();
+            usages.sort_by(|a, b| (&a.file, a.line).cmp(&(&b.file, b.line)));
+            all_usages.push(report::SymbolUsages {
+                symbol: symbol.clone(),
+                usages,
+            });
+        }
+        report.usages = Some(all_usages);
+    }
+
+    let mut selected_tests = Vec::new();
+    if needs_impact {
+        let graph = impact::compute_impact(&index, &all_changed_symbols, args.strict_methods);
+        selected_tests = test_selection::select_tests(&graph, &index.workspace);
+        if args.impact {
+            report.affected = Some(
+                graph
+                    .affected()
+                    .into_iter()
+                    .map(|symbol| report::AffectedSymbol {
+                        symbol: symbol.clone(),
+                        uses: graph.uses(symbol).into_iter().cloned().collect(),
+                    })
+                    .collect(),
+            );
+        }
+        if args.tests {
+            report.tests = Some(selected_tests.clone());
+        }
+    }
+    report::print_report(&report, args.format)?;
+
+    if let Some(Command::Run { runner, junit }) = args.command {
+        let quiet = args.format != report::OutputFormat::Text;
+        let test_run = if report.conclusive {
+            let filters: Vec<String> = selected_tests
+                .iter()
+                .map(|test| test.filter.clone())
+                .collect();
+            // Tests outside of every package can only be reached by testing the whole workspace.
+            let mut packages: Vec<String> = selected_tests
+                .iter()
+                .map(|test| test.package.clone())
+                .collect::<Option<Vec<String>>>()
+                .unwrap_or_default();
+            packages.sort();
+            packages.dedup();
+            if filters.is_empty() {
+                eprintln!("✅ No affected tests to run");
+                runner::TestRun {
+                    code: 0,
+                    results: Vec::new(),
                 }
+            } else {
+                runner::run_tests(
+                    Path::new(&args.path),
+                    runner,
+                    &packages,
+                    Some(&filters),
+                    quiet,
+                )?
             }
-            Err(error) => println!("❌ File {file:?} gives error {error:?}"),
+        } else {
+            eprintln!("⚠️ Analysis is inconclusive, running every test");
+            runner::run_tests(Path::new(&args.path), runner, &[], None, quiet)?
+        };
+        if let Some(junit) = junit {
+            std::fs::write(
+                junit,
+                report::junit::to_junit(&selected_tests, &test_run.results),
+            )?;
+        }
+        if test_run.code != 0 {
+            std::process::exit(test_run.code);
         }
     }
 
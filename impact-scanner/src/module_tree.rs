@@ -0,0 +1,349 @@
+use std::{
+    collections::HashMap,
+    path::{Component, Path, PathBuf},
+};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use tree_sitter::Node;
+
+use crate::{
+    cache::{self, ModuleCache},
+    language::{
+        parsable_language::ParsableLanguage,
+        rust::{attributes_of, RustLanguage},
+    },
+    locals::text,
+    workspace::Package,
+};
+
+#[derive(Debug, Clone)]
+/// Module of a crate stored in its own file.
+///
+/// ## Properties:
+/// * `root` (`std::path::PathBuf`): Root file of the crate, relative to the project root,
+/// * `scope` (`Vec<String>`): Path of the module in its crate, starting with `crate`.
+pub struct ModuleFile {
+    /// Root file of the crate, relative to the project root.
+    pub root: PathBuf,
+    /// Path of the module in its crate, starting with `crate`.
+    pub scope: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Declaration of a module stored in another file (`mod name;`).
+///
+/// ## Properties:
+/// * `inline` (`Vec<(String, Option<String>)>`): Inline modules containing the declaration,
+///   outermost first, with the path given by their `#[path]` attribute,
+/// * `name` (`String`): Name of the declared module,
+/// * `path` (`Option<String>`): Path given by a `#[path = "..."]` attribute, if any.
+pub struct ModDeclaration {
+    /// Inline modules containing the declaration, outermost first, with their `#[path]`.
+    pub inline: Vec<(String, Option<String>)>,
+    /// Name of the declared module.
+    pub name: String,
+    /// Path given by a `#[path = "..."]` attribute, if any.
+    pub path: Option<String>,
+}
+
+#[derive(Debug, Default)]
+/// Modules of every crate of a project, found by following the `mod` declarations from the root
+/// of each crate, as the compiler does.
+///
+/// ## Properties:
+/// * `files` (`HashMap<PathBuf, ModuleFile>`): Module stored in each file reachable from a crate
+///   root, by path relative to the project root.
+pub struct ModuleTree {
+    /// Module stored in each file reachable from a crate root.
+    files: HashMap<PathBuf, ModuleFile>,
+}
+
+/// Gets the file given to a module by a `#[path = "..."]` attribute.
+///
+/// ## Parameters:
+/// * `node` (`tree_sitter::Node`): `mod_item` node,
+/// * `source` (`&str`): Content of the file.
+///
+/// ## Returns:
+/// * (`Option<String>`): Path given by the attribute, None without attribute.
+fn path_attribute(node: Node, source: &str) -> Option<String> {
+    attributes_of(&node, source)
+        .into_iter()
+        .find_map(|attribute| {
+            let attribute: String = attribute.split_whitespace().collect();
+            attribute
+                .strip_prefix("path=\"")?
+                .strip_suffix('"')
+                .map(str::to_string)
+        })
+}
+
+/// Removes the `.` and `..` components of a relative path, without accessing the file system.
+///
+/// ## Parameters:
+/// * `path` (`&std::path::Path`): Path to normalize.
+///
+/// ## Returns:
+/// * (`std::path::PathBuf`): Path without `.` nor `..`, except leading ones.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => (),
+            Component::ParentDir => {
+                if !normalized.pop() {
+                    normalized.push("..");
+                }
+            }
+            _ => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+/// Extracts the `mod name;` declarations of a file, including those of its inline modules.
+///
+/// ## Parameters:
+/// * `source` (`&str`): Content of the file.
+///
+/// ## Returns:
+/// * (`Result<Vec<ModDeclaration>>`): Declarations, error if the file could not be parsed.
+pub fn extract_declarations(source: &str) -> Result<Vec<ModDeclaration>> {
+    let tree = RustLanguage::default().parse(source)?;
+    let mut declarations = Vec::new();
+    collect_declarations(tree.root_node(), source, &[], &mut declarations);
+    Ok(declarations)
+}
+
+/// Collects the `mod name;` declarations under a node, entering inline modules.
+///
+/// ## Parameters:
+/// * `node` (`tree_sitter::Node`): Node to search,
+/// * `source` (`&str`): Content of the file,
+/// * `inline` (`&[(String, Option<String>)]`): Inline modules containing the node,
+/// * `declarations` (`&mut Vec<ModDeclaration>`): Declarations found so far.
+fn collect_declarations(
+    node: Node,
+    source: &str,
+    inline: &[(String, Option<String>)],
+    declarations: &mut Vec<ModDeclaration>,
+) {
+    for child in node.named_children(&mut node.walk()) {
+        if child.kind() != "mod_item" {
+            collect_declarations(child, source, inline, declarations);
+            continue;
+        }
+        let Some(name) = child.child_by_field_name("name") else {
+            continue;
+        };
+        let name = text(name, source).to_string();
+        let path = path_attribute(child, source);
+        match child.child_by_field_name("body") {
+            Some(body) => {
+                let mut inline = inline.to_vec();
+                inline.push((name, path));
+                collect_declarations(body, source, &inline, declarations);
+            }
+            None => declarations.push(ModDeclaration {
+                inline: inline.to_vec(),
+                name,
+                path,
+            }),
+        }
+    }
+}
+
+/// Module stored in a file, not visited yet.
+///
+/// ## Properties:
+/// * `file` (`std::path::PathBuf`): File of the module, relative to the project root,
+/// * `scope` (`Vec<String>`): Path of the module in its crate,
+/// * `owns_directory` (`bool`): true iff the submodules of the module are in the directory of its
+///   file, as for crate roots, `mod.rs` files and files given by a `#[path]` attribute.
+struct PendingModule {
+    /// File of the module, relative to the project root.
+    file: PathBuf,
+    /// Path of the module in its crate.
+    scope: Vec<String>,
+    /// true iff the submodules of the module are in the directory of its file.
+    owns_directory: bool,
+}
+
+/// Finds the file of a declared module.
+/// Submodules of an inline module are in a directory named after it, unless it has a `#[path]`
+/// attribute naming that directory. `#[path]` attributes are relative to the directory of the
+/// file outside of inline modules.
+///
+/// ## Parameters:
+/// * `module` (`&PendingModule`): Module whose file contains the declaration,
+/// * `declaration` (`&ModDeclaration`): Declaration of the submodule,
+/// * `exists` (`impl Fn(&Path) -> bool`): Returns true iff a file exists in the analyzed
+///   version of the project.
+///
+/// ## Returns:
+/// * (`Option<PendingModule>`): Declared module, None if its file does not exist.
+fn resolve_declaration(
+    module: &PendingModule,
+    declaration: &ModDeclaration,
+    exists: impl Fn(&Path) -> bool,
+) -> Option<PendingModule> {
+    let file_dir = module.file.parent().unwrap_or(Path::new("")).to_path_buf();
+    let mut module_dir = match module.owns_directory {
+        true => file_dir.clone(),
+        false => file_dir.join(module.file.file_stem().unwrap_or_default()),
+    };
+    let mut scope = module.scope.clone();
+    for (name, path) in &declaration.inline {
+        module_dir.push(path.as_deref().unwrap_or(name));
+        scope.push(name.clone());
+    }
+    scope.push(declaration.name.clone());
+    let name = &declaration.name;
+    let candidates = match &declaration.path {
+        Some(path) if declaration.inline.is_empty() => vec![(file_dir.join(path), true)],
+        Some(path) => vec![(module_dir.join(path), true)],
+        None => vec![
+            (module_dir.join(format!("{name}.rs")), false),
+            (module_dir.join(name).join("mod.rs"), true),
+        ],
+    };
+    candidates
+        .into_iter()
+        .map(|(file, owns_directory)| (normalize(&file), owns_directory))
+        .find(|(file, _)| exists(file))
+        .map(|(file, owns_directory)| PendingModule {
+            file,
+            scope: scope.clone(),
+            owns_directory,
+        })
+}
+
+impl ModuleTree {
+    /// Follows the `mod` declarations from the root of every crate of the given packages, and
+    /// the build script of each package, in the version of the project the analyzed changes lead
+    /// to. `#[path]` attributes and inline modules are honoured, and `#[cfg]` attributes ignored.
+    /// With the cache, only files whose content changed since the previous run are parsed.
+    ///
+    /// ## Parameters:
+    /// * `project_root` (`&std::path::Path`): Root of the project,
+    /// * `packages` (`&[Package]`): Packages of the project,
+    /// * `sources` (`&HashMap<PathBuf, String>`): Source files of the analyzed version of the
+    ///   project, see `index::read_sources`,
+    /// * `use_cache` (`bool`): true to read and write the cache of the project.
+    ///
+    /// ## Returns:
+    /// * (`ModuleTree`): Module stored in each reachable file.
+    pub fn build(
+        project_root: &Path,
+        packages: &[Package],
+        sources: &HashMap<PathBuf, String>,
+        use_cache: bool,
+    ) -> Self {
+        let mut tree = ModuleTree::default();
+        if packages.is_empty() {
+            return tree;
+        }
+        let exists = |file: &Path| sources.contains_key(file);
+        let previous = match use_cache {
+            true => ModuleCache::load(project_root),
+            false => ModuleCache::new(),
+        };
+        let mut cache = ModuleCache::new();
+        let roots = packages.iter().flat_map(|package| {
+            package
+                .targets
+                .iter()
+                .map(|target| target.root.clone())
+                .chain(std::iter::once(package.dir.join("build.rs")))
+        });
+        for root in roots {
+            let mut pending = vec![PendingModule {
+                file: root.clone(),
+                scope: vec!["crate".to_string()],
+                owns_directory: true,
+            }];
+            while let Some(module) = pending.pop() {
+                if tree.files.contains_key(&module.file) {
+                    continue;
+                }
+                let Some(source) = sources.get(&module.file) else {
+                    continue;
+                };
+                let Ok(hash) = cache::content_hash(source) else {
+                    continue;
+                };
+                let declarations = match previous.get(&hash) {
+                    Some(declarations) => declarations.clone(),
+                    None => match extract_declarations(source) {
+                        Ok(declarations) => declarations,
+                        Err(error) => {
+                            eprintln!("❌ File {:?} gives error {error:?}", module.file);
+                            Vec::new()
+                        }
+                    },
+                };
+                pending.extend(
+                    declarations.iter().filter_map(|declaration| {
+                        resolve_declaration(&module, declaration, exists)
+                    }),
+                );
+                cache.insert(hash, declarations);
+                tree.files.insert(
+                    module.file,
+                    ModuleFile {
+                        root: root.clone(),
+                        scope: module.scope,
+                    },
+                );
+            }
+        }
+        if use_cache {
+            if let Err(error) = cache.save(project_root) {
+                eprintln!("⚠️ Could not write the cache: {error}");
+            }
+        }
+        tree
+    }
+
+    /// Module stored in a file.
+    ///
+    /// ## Parameters:
+    /// * `file` (`&std::path::Path`): Path of the file, relative to the project root.
+    ///
+    /// ## Returns:
+    /// * (`Option<&ModuleFile>`): Module of the file, None if no crate root reaches it.
+    pub fn module_of(&self, file: &Path) -> Option<&ModuleFile> {
+        self.files.get(file)
+    }
+
+    /// Lists the Rust files of the packages that no crate root reaches, and that the compiler
+    /// ignores, in the version of the project the analyzed changes lead to.
+    ///
+    /// ## Parameters:
+    /// * `packages` (`&[Package]`): Packages of the project,
+    /// * `sources` (`&HashMap<PathBuf, String>`): Source files of the analyzed version of the
+    ///   project, see `index::read_sources`.
+    ///
+    /// ## Returns:
+    /// * (`Vec<std::path::PathBuf>`): Unreachable files, relative to the project root, sorted.
+    pub fn unreachable(
+        &self,
+        packages: &[Package],
+        sources: &HashMap<PathBuf, String>,
+    ) -> Vec<PathBuf> {
+        let mut unreachable: Vec<PathBuf> = sources
+            .keys()
+            .filter(|file| {
+                file.extension().is_some_and(|extension| extension == "rs")
+                    && packages
+                        .iter()
+                        .any(|package| file.starts_with(&package.dir))
+                    && !self.files.contains_key(*file)
+            })
+            .cloned()
+            .collect();
+        unreachable.sort();
+        unreachable
+    }
+}
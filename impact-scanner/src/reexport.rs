@@ -0,0 +1,165 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::PathBuf,
+    rc::Rc,
+};
+
+use crate::{
+    index::FileIndex,
+    language::{get_language_for_file, parsable_language::ParsableLanguage},
+    usage,
+    visibility::Visibility,
+    workspace::Workspace,
+};
+
+/// Maximum number of re-exports followed to find the definition of a path.
+const MAX_HOPS: usize = 8;
+
+#[derive(Debug, Default)]
+/// Paths under which symbols are re-exported with `pub use`, across every crate of a project.
+///
+/// ## Properties:
+/// * `aliases` (`HashMap<Vec<String>, Vec<String>>`): Path imported by each re-export, by path
+///   of the re-export, both absolute,
+/// * `globs` (`HashMap<Vec<String>, Vec<Vec<String>>>`): Modules whose items are all
+///   re-exported by a module (`pub use module::*`), by path of the re-exporting module,
+/// * `items` (`HashSet<Vec<String>>`): Absolute path of every definition, which take precedence
+///   over glob re-exports,
+/// * `renamed` (`HashSet<String>`): Names given to symbols by re-exports renaming them.
+pub struct Reexports {
+    /// Path imported by each re-export, by path of the re-export, both absolute.
+    aliases: HashMap<Vec<String>, Vec<String>>,
+    /// Modules whose items are all re-exported by a module, by path of the re-exporting module.
+    globs: HashMap<Vec<String>, Vec<Vec<String>>>,
+    /// Absolute path of every definition.
+    items: HashSet<Vec<String>>,
+    /// Names given to symbols by re-exports renaming them.
+    renamed: HashSet<String>,
+}
+
+impl Reexports {
+    /// Collects the re-exports of every indexed file.
+    /// Imports that are not visible outside of their module are left out.
+    ///
+    /// ## Parameters:
+    /// * `files` (`&HashMap<PathBuf, FileIndex>`): Index of each source file,
+    /// * `workspace` (`&Rc<Workspace>`): Packages of the project.
+    ///
+    /// ## Returns:
+    /// * (`Reexports`): Re-exports of the project.
+    pub fn collect(files: &HashMap<PathBuf, FileIndex>, workspace: &Rc<Workspace>) -> Self {
+        let mut reexports = Reexports::default();
+        for (file, file_index) in files {
+            let module = get_language_for_file(file, workspace).scope_from_path(file);
+            reexports.items.extend(
+                file_index
+                    .definitions
+                    .iter()
+                    .map(|definition| workspace.absolute_scope(file, &definition.symbol.scope)),
+            );
+            for glob in &file_index.globs {
+                if glob.visibility != Visibility::Private {
+                    reexports
+                        .globs
+                        .entry(workspace.absolute_scope(file, &module))
+                        .or_default()
+                        .push(workspace.absolute_scope(file, &glob.scope));
+                }
+            }
+            for (name, imported_symbol) in &file_index.use_map {
+                if imported_symbol.visibility == Visibility::Private {
+                    continue;
+                }
+                let mut alias = module.clone();
+                alias.push(name.clone());
+                let mut target = imported_symbol.scope.clone();
+                usage::resolve_with_use_map(&mut target, &file_index.use_map);
+                let alias = workspace.absolute_scope(file, &alias);
+                let target = workspace.absolute_scope(file, &target);
+                if alias == target {
+                    // Declaration of a module.
+                    continue;
+                }
+                if target.last() != Some(name) {
+                    reexports.renamed.insert(name.clone());
+                }
+                reexports.aliases.insert(alias, target);
+            }
+        }
+        reexports
+    }
+
+    /// Follows the re-exports a path goes through, up to the path of the definition.
+    /// Explicit re-exports and definitions take precedence over glob re-exports.
+    ///
+    /// ## Parameters:
+    /// * `scope` (`&[String]`): Absolute path of a used symbol.
+    ///
+    /// ## Returns:
+    /// * (`Vec<String>`): Path without re-exports, the given path if it has none.
+    pub fn canonical(&self, scope: &[String]) -> Vec<String> {
+        self.follow(scope, MAX_HOPS)
+    }
+
+    /// Follows at most a given number of re-exports from a path.
+    ///
+    /// ## Parameters:
+    /// * `scope` (`&[String]`): Absolute path of a used symbol,
+    /// * `hops` (`usize`): Maximum number of re-exports to follow.
+    ///
+    /// ## Returns:
+    /// * (`Vec<String>`): Path reached after following the re-exports.
+    fn follow(&self, scope: &[String], hops: usize) -> Vec<String> {
+        let mut scope = scope.to_vec();
+        for hops_left in (0..hops).rev() {
+            if let Some((length, target)) = (1..=scope.len())
+                .rev()
+                .find_map(|length| Some((length, self.aliases.get(&scope[..length])?)))
+            {
+                scope.splice(0..length, target.iter().cloned());
+                continue;
+            }
+            if self.items.contains(&scope) {
+                break;
+            }
+            return (1..scope.len())
+                .rev()
+                .find_map(|length| {
+                    self.globs
+                        .get(&scope[..length])?
+                        .iter()
+                        .map(|module| {
+                            let mut candidate = module.clone();
+                            candidate.extend(scope[length..].iter().cloned());
+                            self.follow(&candidate, hops_left)
+                        })
+                        .find(|candidate| self.items.contains(candidate))
+                })
+                .unwrap_or(scope);
+        }
+        scope
+    }
+
+    /// Returns true iff an absolute path is the path of a definition of the project.
+    ///
+    /// ## Parameters:
+    /// * `scope` (`&[String]`): Absolute path to check.
+    ///
+    /// ## Returns:
+    /// * (`bool`): true iff a symbol of the project is defined at this path.
+    pub fn is_definition(&self, scope: &[String]) -> bool {
+        self.items.contains(scope)
+    }
+
+    /// Returns true iff a name may designate a symbol with another name, through a re-export
+    /// renaming it.
+    ///
+    /// ## Parameters:
+    /// * `name` (`&str`): Name of a used symbol.
+    ///
+    /// ## Returns:
+    /// * (`bool`): true iff a re-export gives this name to a symbol.
+    pub fn is_renamed(&self, name: &str) -> bool {
+        self.renamed.contains(name)
+    }
+}
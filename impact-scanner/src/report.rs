@@ -0,0 +1,246 @@
+use anyhow::Result;
+use clap::ValueEnum;
+use serde::Serialize;
+use std::path::PathBuf;
+
+use crate::{symbol::Symbol, test_selection::SelectedTest, usage::Usage};
+
+pub mod junit;
+mod sarif;
+
+/// Version of the JSON schema, incremented on every breaking change of `Report`.
+pub const SCHEMA_VERSION: u32 = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+/// Format of the results printed on the standard output.
+pub enum OutputFormat {
+    /// Human readable text.
+    Text,
+    /// JSON document following the versioned `Report` schema.
+    Json,
+    /// SARIF log, to display changed and affected symbols as annotations of a diff.
+    Sarif,
+}
+
+#[derive(Debug, Serialize)]
+/// Changed symbols of a file.
+///
+/// ## Properties:
+/// * `file` (`std::path::PathBuf`): Name of the file,
+/// * `symbols` (`Vec<Symbol>`): Symbols changed in the file,
+/// * `error` (`Option<String>`): Reason why the file could not be analyzed, if any,
+/// * `skipped` (`bool`): true iff the file is not a source file of a known language.
+pub struct FileReport {
+    /// Name of the file.
+    pub file: PathBuf,
+    /// Symbols changed in the file.
+    pub symbols: Vec<Symbol>,
+    /// Reason why the file could not be analyzed, if any.
+    pub error: Option<String>,
+    /// true iff the file is not a source file of a known language.
+    pub skipped: bool,
+}
+
+#[derive(Debug, Serialize)]
+/// Places where a changed symbol is used.
+///
+/// ## Properties:
+/// * `symbol` (`Symbol`): Changed symbol,
+/// * `usages` (`Vec<Usage>`): Places where the symbol is used, sorted by file and line.
+pub struct SymbolUsages {
+    /// Changed symbol.
+    pub symbol: Symbol,
+    /// Places where the symbol is used, sorted by file and line.
+    pub usages: Vec<Usage>,
+}
+
+#[derive(Debug, Serialize)]
+/// Symbol affected by the changes.
+///
+/// ## Properties:
+/// * `symbol` (`Symbol`): Affected symbol,
+/// * `uses` (`Vec<Symbol>`): Changed or affected symbols it uses.
+pub struct AffectedSymbol {
+    /// Affected symbol.
+    pub symbol: Symbol,
+    /// Changed or affected symbols it uses.
+    pub uses: Vec<Symbol>,
+}
+
+#[derive(Debug, Serialize)]
+/// Results of an analysis.
+/// Optional parts are None when they were not requested.
+///
+/// ## Properties:
+/// * `version` (`u32`): Version of the schema, `SCHEMA_VERSION`,
+/// * `conclusive` (`bool`): false if some changed files could not be analyzed,
+/// * `files` (`Vec<FileReport>`): Changed symbols of every changed file,
+/// * `usages` (`Option<Vec<SymbolUsages>>`): Usages of every changed symbol,
+/// * `affected` (`Option<Vec<AffectedSymbol>>`): Symbols affected by the changes,
+/// * `tests` (`Option<Vec<SelectedTest>>`): Tests affected by the changes,
+/// * `unreachable` (`Option<Vec<PathBuf>>`): Rust files of the packages that no crate root
+///   reaches, and that the compiler ignores.
+pub struct Report {
+    /// Version of the schema, `SCHEMA_VERSION`.
+    pub version: u32,
+    /// false if some changed files could not be analyzed.
+    pub conclusive: bool,
+    /// Changed symbols of every changed file.
+    pub files: Vec<FileReport>,
+    /// Usages of every changed symbol.
+    pub usages: Option<Vec<SymbolUsages>>,
+    /// Symbols affected by the changes.
+    pub affected: Option<Vec<AffectedSymbol>>,
+    /// Tests affected by the changes.
+    pub tests: Option<Vec<SelectedTest>>,
+    /// Rust files of the packages that no crate root reaches.
+    pub unreachable: Option<Vec<PathBuf>>,
+}
+
+impl Default for Report {
+    fn default() -> Self {
+        Self {
+            version: SCHEMA_VERSION,
+            conclusive: true,
+            files: Vec::new(),
+            usages: None,
+            affected: None,
+            tests: None,
+            unreachable: None,
+        }
+    }
+}
+
+/// Prints a report as human readable text.
+///
+/// ## Parameters:
+/// * `report` (`&Report`): Results of the analysis.
+fn print_text(report: &Report) {
+    for file_report in &report.files {
+        let file = &file_report.file;
+        match &file_report.error {
+            None if file_report.skipped => println!("⏭️ Skipped {file:?}, not a source file"),
+            None => {
+                println!("✏️ Changed symbols in {file:?}:");
+                for symbol in &file_report.symbols {
+                    println!("   - {symbol},");
+                    let usages = report
+                        .usages
+                        .iter()
+                        .flatten()
+                        .filter(|usages| &usages.symbol == symbol)
+                        .flat_map(|usages| &usages.usages);
+                    for usage in usages {
+                        println!("      used in {}:{}", usage.file.display(), usage.line);
+                    }
+                }
+            }
+            Some(error) => println!("❌ File {file:?} gives error {error}"),
+        }
+    }
+    if let Some(affected) = &report.affected {
+        println!("💥 Affected symbols:");
+        for affected_symbol in affected {
+            let uses: Vec<String> = affected_symbol.uses.iter().map(|s| s.name()).collect();
+            println!("   - {}, uses {}", affected_symbol.symbol, uses.join(", "));
+        }
+    }
+    if let Some(tests) = &report.tests {
+        println!("🧪 Affected tests:");
+        for test in tests {
+            println!(
+                "   - {} ({}:{})",
+                test.filter,
+                test.symbol.file.display(),
+                test.symbol.line
+            );
+        }
+    }
+    if let Some(unreachable) = &report.unreachable {
+        println!("👻 Files unreachable from every crate root:");
+        for file in unreachable {
+            println!("   - {}", file.display());
+        }
+    }
+}
+
+/// Prints a report on the standard output.
+///
+/// ## Parameters:
+/// * `report` (`&Report`): Results of the analysis,
+/// * `format` (`OutputFormat`): Format to print the report in.
+///
+/// ## Returns:
+/// * (`Result<()>`): Error if the report could not be serialized.
+pub fn print_report(report: &Report, format: OutputFormat) -> Result<()> {
+    match format {
+        OutputFormat::Text => print_text(report),
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(report)?),
+        OutputFormat::Sarif => println!(
+            "{}",
+            serde_json::to_string_pretty(&sarif::to_sarif(report))?
+        ),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+/// Report of a fixed analysis, used by the golden-file tests of the output formats: a modified
+/// function, a deleted function, a struct affected by the modified one, and two tests.
+///
+/// ## Returns:
+/// * (`Report`): Report with changed symbols, affected symbols and selected tests.
+pub(crate) fn fixed_report() -> Report {
+    use crate::{
+        symbol::SymbolBuilder, symbol_kind::SymbolKind, symbol_status::SymbolStatus,
+        visibility::Visibility,
+    };
+
+    let symbol = |file: &str, line: usize, kind: SymbolKind, scope: &[&str]| {
+        SymbolBuilder::create()
+            .withLine(line)
+            .withFile(PathBuf::from(file))
+            .withKind(kind)
+            .withVisibility(Visibility::Public)
+            .withScope(scope.iter().map(|part| part.to_string()).collect())
+            .build()
+    };
+    let changed = Symbol {
+        status: SymbolStatus::Modified,
+        ..symbol("src/lib.rs", 3, SymbolKind::Function, &["crate", "parse"])
+    };
+    let deleted = Symbol {
+        status: SymbolStatus::Deleted,
+        ..symbol("src/lib.rs", 40, SymbolKind::Function, &["crate", "legacy"])
+    };
+    let config = symbol(
+        "src/config.rs",
+        10,
+        SymbolKind::Struct,
+        &["crate", "config", "Config"],
+    );
+    let test = |line: usize, name: &str| SelectedTest {
+        symbol: symbol(
+            "src/lib.rs",
+            line,
+            SymbolKind::Test,
+            &["crate", "tests", name],
+        ),
+        filter: format!("tests::{name}"),
+        package: Some("demo".to_string()),
+    };
+    Report {
+        files: vec![FileReport {
+            file: PathBuf::from("src/lib.rs"),
+            symbols: vec![changed.clone(), deleted],
+            error: None,
+            skipped: false,
+        }],
+        affected: Some(vec![AffectedSymbol {
+            symbol: config,
+            uses: vec![changed],
+        }]),
+        tests: Some(vec![test(20, "parses_empty"), test(25, "parses_<nested>")]),
+        ..Default::default()
+    }
+}
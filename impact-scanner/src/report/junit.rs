@@ -0,0 +1,139 @@
+use std::fmt::Write;
+
+use crate::{
+    runner::{TestOutcome, TestResult},
+    test_selection::SelectedTest,
+};
+
+/// Escapes a text to be used as an XML attribute value.
+///
+/// ## Parameters:
+/// * `text` (`&str`): Text to escape.
+///
+/// ## Returns:
+/// * (`String`): Escaped text.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+/// Test case of the JUnit report.
+struct TestCase {
+    /// Name of the test in its test binary.
+    name: String,
+    /// File and line of the test function, if known.
+    location: Option<(String, usize)>,
+    /// Outcome of the test, None if it was selected but not run.
+    outcome: Option<TestOutcome>,
+}
+
+/// Builds a JUnit XML report of the selected tests and their outcomes.
+/// Selected tests without outcome are reported as skipped. When the whole test suite was run,
+/// tests that were not selected are reported too.
+///
+/// ## Parameters:
+/// * `tests` (`&[SelectedTest]`): Tests selected by the analysis,
+/// * `results` (`&[TestResult]`): Outcomes reported by the test runner.
+///
+/// ## Returns:
+/// * (`String`): JUnit XML document.
+pub fn to_junit(tests: &[SelectedTest], results: &[TestResult]) -> String {
+    let outcome_of = |name: &str| {
+        results
+            .iter()
+            .find(|result| result.name == name)
+            .map(|result| result.outcome)
+    };
+    let mut cases: Vec<TestCase> = tests
+        .iter()
+        .map(|test| TestCase {
+            name: test.filter.clone(),
+            location: Some((
+                test.symbol.file.to_string_lossy().to_string(),
+                test.symbol.line,
+            )),
+            outcome: outcome_of(&test.filter),
+        })
+        .collect();
+    for result in results {
+        if !tests.iter().any(|test| test.filter == result.name) {
+            cases.push(TestCase {
+                name: result.name.clone(),
+                location: None,
+                outcome: Some(result.outcome),
+            });
+        }
+    }
+    let failures = cases
+        .iter()
+        .filter(|case| case.outcome == Some(TestOutcome::Failed))
+        .count();
+    let skipped = cases
+        .iter()
+        .filter(|case| matches!(case.outcome, None | Some(TestOutcome::Ignored)))
+        .count();
+
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    let totals = format!(
+        "tests=\"{}\" failures=\"{failures}\" errors=\"0\" skipped=\"{skipped}\"",
+        cases.len()
+    );
+    let _ = writeln!(xml, "<testsuites name=\"impact-scanner\" {totals}>");
+    let _ = writeln!(xml, "  <testsuite name=\"impact-scanner\" {totals}>");
+    for case in &cases {
+        let name = escape(&case.name);
+        let location = match &case.location {
+            Some((file, line)) => format!(
+                " classname=\"{}\" file=\"{}\" line=\"{line}\"",
+                escape(file),
+                escape(file)
+            ),
+            None => String::new(),
+        };
+        let _ = write!(xml, "    <testcase name=\"{name}\"{location}");
+        let _ = match case.outcome {
+            Some(TestOutcome::Passed) => writeln!(xml, "/>"),
+            Some(TestOutcome::Failed) => writeln!(
+                xml,
+                ">\n      <failure message=\"test failed\"/>\n    </testcase>"
+            ),
+            Some(TestOutcome::Ignored) => writeln!(
+                xml,
+                ">\n      <skipped message=\"ignored\"/>\n    </testcase>"
+            ),
+            None => writeln!(
+                xml,
+                ">\n      <skipped message=\"not run\"/>\n    </testcase>"
+            ),
+        };
+    }
+    xml.push_str("  </testsuite>\n</testsuites>\n");
+    xml
+}
+
+#[cfg(test)]
+mod tests {
+    use super::to_junit;
+    use crate::{
+        report::fixed_report,
+        runner::{TestOutcome, TestResult},
+    };
+
+    #[test]
+    fn to_junit_matches_golden_file() {
+        let tests = fixed_report().tests.unwrap();
+        let results = [
+            ("tests::parses_empty", TestOutcome::Failed),
+            ("tests::unselected_passes", TestOutcome::Passed),
+            ("tests::unselected_ignored", TestOutcome::Ignored),
+        ]
+        .map(|(name, outcome)| TestResult {
+            name: name.to_string(),
+            outcome,
+        });
+        assert_eq!(to_junit(&tests, &results), include_str!("golden/junit.xml"));
+    }
+}
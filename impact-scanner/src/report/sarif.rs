@@ -0,0 +1,134 @@
+use serde_json::{json, Value};
+
+use crate::{symbol::Symbol, symbol_status::SymbolStatus};
+
+use super::Report;
+
+/// Version of the SARIF specification the output follows.
+const SARIF_VERSION: &str = "2.1.0";
+
+/// Schema of the SARIF specification the output follows.
+const SARIF_SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+
+/// Builds a SARIF result pointing at a symbol.
+/// Deleted symbols point at their file only: their line is numbered in the old version of the
+/// file, while results are displayed on the new one.
+///
+/// ## Parameters:
+/// * `rule_id` (`&str`): Identifier of the rule the result belongs to,
+/// * `symbol` (`&Symbol`): Symbol the result is about,
+/// * `message` (`String`): Text displayed with the result.
+///
+/// ## Returns:
+/// * (`serde_json::Value`): SARIF `result` object.
+fn sarif_result(rule_id: &str, symbol: &Symbol, message: String) -> Value {
+    let mut physical_location = json!({
+        "artifactLocation": {
+            "uri": symbol.file.to_string_lossy().replace('\\', "/"),
+            "uriBaseId": "%SRCROOT%",
+        },
+    });
+    if symbol.status != SymbolStatus::Deleted {
+        physical_location["region"] = json!({ "startLine": symbol.line });
+    }
+    json!({
+        "ruleId": rule_id,
+        "level": "note",
+        "message": { "text": message },
+        "locations": [{
+            "physicalLocation": physical_location,
+            "logicalLocations": [{
+                "fullyQualifiedName": symbol.scope.join("::"),
+                "name": symbol.name(),
+            }],
+        }],
+    })
+}
+
+/// Converts a report to a SARIF log, so changed and affected symbols can be displayed as
+/// annotations of a diff.
+///
+/// ## Parameters:
+/// * `report` (`&Report`): Results of the analysis.
+///
+/// ## Returns:
+/// * (`serde_json::Value`): SARIF log with one run of impact-scanner.
+pub fn to_sarif(report: &Report) -> Value {
+    let mut results = Vec::new();
+    for symbol in report.files.iter().flat_map(|file| &file.symbols) {
+        results.push(sarif_result(
+            "changed-symbol",
+            symbol,
+            format!(
+                "{:?} `{}` is {:?}",
+                symbol.kind,
+                symbol.name(),
+                symbol.status
+            )
+            .to_lowercase(),
+        ));
+    }
+    for affected in report.affected.iter().flatten() {
+        let uses: Vec<String> = affected.uses.iter().map(|s| s.name()).collect();
+        results.push(sarif_result(
+            "affected-symbol",
+            &affected.symbol,
+            format!(
+                "`{}` is affected by the changes, through `{}`",
+                affected.symbol.name(),
+                uses.join("`, `")
+            ),
+        ));
+    }
+    for test in report.tests.iter().flatten() {
+        results.push(sarif_result(
+            "affected-test",
+            &test.symbol,
+            format!("Test `{}` is affected by the changes", test.filter),
+        ));
+    }
+    json!({
+        "$schema": SARIF_SCHEMA,
+        "version": SARIF_VERSION,
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": env!("CARGO_PKG_NAME"),
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "rules": [
+                        {
+                            "id": "changed-symbol",
+                            "shortDescription": { "text": "Symbol changed by the diff" },
+                        },
+                        {
+                            "id": "affected-symbol",
+                            "shortDescription": { "text": "Symbol using a changed symbol" },
+                        },
+                        {
+                            "id": "affected-test",
+                            "shortDescription": { "text": "Test affected by the changes" },
+                        },
+                    ],
+                },
+            },
+            "results": results,
+        }],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use super::to_sarif;
+    use crate::report::fixed_report;
+
+    #[test]
+    fn to_sarif_matches_golden_file() {
+        let mut expected: Value =
+            serde_json::from_str(include_str!("golden/report.sarif")).unwrap();
+        // The golden file does not change with the version of the tool.
+        expected["runs"][0]["tool"]["driver"]["version"] = json!(env!("CARGO_PKG_VERSION"));
+        assert_eq!(to_sarif(&fixed_report()), expected);
+    }
+}
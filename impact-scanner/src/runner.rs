@@ -0,0 +1,251 @@
+use anyhow::{anyhow, bail, Result};
+use clap::ValueEnum;
+use std::{
+    io::{BufRead, BufReader, Read},
+    path::Path,
+    process::{Command, Stdio},
+    thread::{self, JoinHandle},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+/// Tool used to run the tests.
+pub enum TestRunner {
+    /// `cargo test`, using the default test harness.
+    Cargo,
+    /// `cargo nextest run`.
+    Nextest,
+}
+
+/// Builds the command running the given tests.
+///
+/// ## Parameters:
+/// * `runner` (`TestRunner`): Tool used to run the tests,
+/// * `packages` (`&[String]`): Packages containing the tests, every package of the workspace if
+///   empty,
+/// * `filters` (`Option<&[String]>`): Exact names of the tests to run, or None to run the whole
+///   test suite.
+///
+/// ## Returns:
+/// * (`std::process::Command`): Command to spawn, without working directory.
+fn test_command(runner: TestRunner, packages: &[String], filters: Option<&[String]>) -> Command {
+    let mut command = Command::new("cargo");
+    match runner {
+        TestRunner::Cargo => command.arg("test"),
+        TestRunner::Nextest => command.args(["nextest", "run"]),
+    };
+    match packages.is_empty() {
+        true => command.arg("--workspace"),
+        false => command.args(packages.iter().flat_map(|package| ["-p", package.as_str()])),
+    };
+    if let Some(filters) = filters {
+        command.arg("--").arg("--exact").args(filters);
+    }
+    command
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Outcome of a single test.
+pub enum TestOutcome {
+    /// The test passed.
+    Passed,
+    /// The test failed.
+    Failed,
+    /// The test was ignored or skipped by the runner.
+    Ignored,
+}
+
+#[derive(Debug, Clone)]
+/// Outcome of a test, as reported by the test runner.
+///
+/// ## Properties:
+/// * `name` (`String`): Name of the test in its test binary,
+/// * `outcome` (`TestOutcome`): Outcome of the test.
+pub struct TestResult {
+    /// Name of the test in its test binary.
+    pub name: String,
+    /// Outcome of the test.
+    pub outcome: TestOutcome,
+}
+
+#[derive(Debug, Clone)]
+/// Result of a run of the tests.
+///
+/// ## Properties:
+/// * `code` (`i32`): Exit code of the test command, 0 iff every test passed,
+/// * `results` (`Vec<TestResult>`): Outcome of every test reported by the runner.
+pub struct TestRun {
+    /// Exit code of the test command, 0 iff every test passed.
+    pub code: i32,
+    /// Outcome of every test reported by the runner.
+    pub results: Vec<TestResult>,
+}
+
+/// Parses a line printed by a test runner.
+/// Understands the lines of the default test harness (`test name ... ok`) and of nextest
+/// (`PASS [   0.004s] crate name`).
+///
+/// ## Parameters:
+/// * `line` (`&str`): Line printed by the runner.
+///
+/// ## Returns:
+/// * (`Option<TestResult>`): Outcome of a test if the line reports one.
+fn parse_test_line(line: &str) -> Option<TestResult> {
+    if let Some(rest) = line.strip_prefix("test ") {
+        let (name, status) = rest.rsplit_once(" ... ")?;
+        let outcome = match status.trim() {
+            "ok" => TestOutcome::Passed,
+            "FAILED" => TestOutcome::Failed,
+            status if status.starts_with("ignored") => TestOutcome::Ignored,
+            _ => return None,
+        };
+        return Some(TestResult {
+            name: name.to_string(),
+            outcome,
+        });
+    }
+    let (status, rest) = line.trim_start().split_once(' ')?;
+    let outcome = match status {
+        "PASS" => TestOutcome::Passed,
+        "FAIL" | "TIMEOUT" | "SIGABRT" | "SIGSEGV" | "ABORT" => TestOutcome::Failed,
+        "SKIP" => TestOutcome::Ignored,
+        _ => return None,
+    };
+    let name = rest.split_whitespace().last()?;
+    Some(TestResult {
+        name: name.to_string(),
+        outcome,
+    })
+}
+
+/// Forwards the lines of an output of the test runner while parsing test outcomes.
+///
+/// ## Parameters:
+/// * `reader` (`R`): Output of the test runner,
+/// * `to_stderr` (`bool`): true to forward lines to the standard error, else to the standard
+///   output.
+///
+/// ## Returns:
+/// * (`JoinHandle<Vec<TestResult>>`): Thread returning the outcomes found in the output.
+fn forward_and_parse<R: Read + Send + 'static>(
+    reader: R,
+    to_stderr: bool,
+) -> JoinHandle<Vec<TestResult>> {
+    thread::spawn(move || {
+        let mut results = Vec::new();
+        for line in BufReader::new(reader).lines().map_while(Result::ok) {
+            match to_stderr {
+                true => eprintln!("{line}"),
+                false => println!("{line}"),
+            }
+            results.extend(parse_test_line(&line));
+        }
+        results
+    })
+}
+
+/// Runs the given tests, streaming their output.
+///
+/// ## Parameters:
+/// * `directory` (`&std::path::Path`): Directory of the project to test, cargo is run from it,
+/// * `runner` (`TestRunner`): Tool used to run the tests,
+/// * `packages` (`&[String]`): Packages containing the tests, every package of the workspace if
+///   empty,
+/// * `filters` (`Option<&[String]>`): Exact names of the tests to run, or None to run the whole
+///   test suite,
+/// * `quiet` (`bool`): true to redirect the output of the tests to the standard error, keeping the
+///   standard output for the report.
+///
+/// ## Returns:
+/// * (`Result<TestRun>`): Exit code of the test command and outcome of every test.
+pub fn run_tests(
+    directory: &Path,
+    runner: TestRunner,
+    packages: &[String],
+    filters: Option<&[String]>,
+    quiet: bool,
+) -> Result<TestRun> {
+    let mut child = test_command(runner, packages, filters)
+        .current_dir(directory)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let (Some(stdout), Some(stderr)) = (child.stdout.take(), child.stderr.take()) else {
+        bail!("can not capture the output of the tests")
+    };
+    let stdout = forward_and_parse(stdout, quiet);
+    let stderr = forward_and_parse(stderr, true);
+    let status = child.wait()?;
+    let mut results = stdout
+        .join()
+        .map_err(|_| anyhow!("can not read the output of the tests"))?;
+    results.extend(
+        stderr
+            .join()
+            .map_err(|_| anyhow!("can not read the output of the tests"))?,
+    );
+    Ok(TestRun {
+        code: status.code().unwrap_or(1),
+        results,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_test_line, TestOutcome};
+
+    /// Parses a line that reports a test, panicking otherwise.
+    ///
+    /// ## Parameters:
+    /// * `line` (`&str`): Line printed by the runner.
+    ///
+    /// ## Returns:
+    /// * (`(String, TestOutcome)`): Name and outcome of the test.
+    fn parse(line: &str) -> (String, TestOutcome) {
+        let result = parse_test_line(line).expect("line should report a test");
+        (result.name, result.outcome)
+    }
+
+    #[test]
+    fn parses_cargo_lines() {
+        assert_eq!(
+            parse("test tests::parses_empty ... ok"),
+            ("tests::parses_empty".to_string(), TestOutcome::Passed)
+        );
+        assert_eq!(
+            parse("test tests::parses_empty ... FAILED"),
+            ("tests::parses_empty".to_string(), TestOutcome::Failed)
+        );
+        assert_eq!(
+            parse("test tests::slow ... ignored"),
+            ("tests::slow".to_string(), TestOutcome::Ignored)
+        );
+        assert_eq!(
+            parse("test tests::slow ... ignored, needs a database"),
+            ("tests::slow".to_string(), TestOutcome::Ignored)
+        );
+    }
+
+    #[test]
+    fn parses_nextest_lines() {
+        assert_eq!(
+            parse("        PASS [   0.004s] demo tests::parses_empty"),
+            ("tests::parses_empty".to_string(), TestOutcome::Passed)
+        );
+        assert_eq!(
+            parse("        FAIL [   0.010s] demo::integration tests::parses_empty"),
+            ("tests::parses_empty".to_string(), TestOutcome::Failed)
+        );
+        assert_eq!(
+            parse("        SKIP [         ] demo tests::slow"),
+            ("tests::slow".to_string(), TestOutcome::Ignored)
+        );
+    }
+
+    #[test]
+    fn ignores_other_lines() {
+        assert!(parse_test_line("running 3 tests").is_none());
+        assert!(parse_test_line("test result: ok. 3 passed; 0 failed").is_none());
+        assert!(parse_test_line("     Summary [   0.012s] 3 tests run: 3 passed").is_none());
+        assert!(parse_test_line("    Starting 3 tests across 1 binary").is_none());
+    }
+}
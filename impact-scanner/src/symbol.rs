@@ -1,8 +1,10 @@
 use anyhow::Result;
 use impact_scanner_derive::TestBuilder;
+use serde::{Deserialize, Serialize};
 use std::{
     collections::HashSet,
     fmt::{Display, Formatter},
+    hash::{Hash, Hasher},
     path::PathBuf,
 };
 use tree_sitter::{Node, Tree};
@@ -10,9 +12,11 @@ use tree_sitter::{Node, Tree};
 use crate::{
     language::{parsable_language::ParsableLanguage, Languages},
     symbol_kind::SymbolKind,
+    symbol_status::SymbolStatus,
+    visibility::Visibility,
 };
 
-#[derive(Debug, Clone, TestBuilder)]
+#[derive(Debug, Clone, Serialize, Deserialize, TestBuilder)]
 /// Symbol extracted from a source file.
 ///
 /// ## Properties:
@@ -20,8 +24,14 @@ use crate::{
 /// * `line` (`usize`): Line number where the symbol is named,
 /// * `file` (`std::path::PathBuf`): Name of the file the symbol was found in.
 /// * `kind` (`symbol_kind::SymbolKind`): Kind of symbol (eg. function),
-/// * `is_exported` (`bool`): true iff the symbol is usable from outside of the current scope.
+/// * `visibility` (`visibility::Visibility`): Where the symbol can be used from.
 /// * `scope` (`Vec<String>`): Hierarchical scope (e.g., modules, classes) where the symbol is defined.
+/// * `status` (`symbol_status::SymbolStatus`): How the symbol was affected by the changes.
+/// * `old_file` (`Option<std::path::PathBuf>`): Previous name of the file, if it was renamed or
+///   moved by the changes.
+///
+/// Two symbols are equal when they designate the same definition, whatever their status and
+/// previous file.
 pub struct Symbol {
     /// Alias of the imported symbol, if any.
     pub naming: Option<String>,
@@ -32,10 +42,38 @@ pub struct Symbol {
     #[builder(default = SymbolKind::Function)]
     /// Kind of symbol (eg. function).
     pub kind: SymbolKind,
-    /// true iff the symbol is usable from outside of the current scope.
-    pub is_exported: bool,
+    /// Where the symbol can be used from.
+    pub visibility: Visibility,
     /// Hierarchical scope (e.g., modules, classes) where the symbol is defined.
     pub scope: Vec<String>,
+    /// How the symbol was affected by the changes.
+    pub status: SymbolStatus,
+    /// Previous name of the file, if it was renamed or moved by the changes.
+    pub old_file: Option<PathBuf>,
+}
+
+impl PartialEq for Symbol {
+    fn eq(&self, other: &Self) -> bool {
+        self.naming == other.naming
+            && self.line == other.line
+            && self.file == other.file
+            && self.kind == other.kind
+            && self.visibility == other.visibility
+            && self.scope == other.scope
+    }
+}
+
+impl Eq for Symbol {}
+
+impl Hash for Symbol {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.naming.hash(state);
+        self.line.hash(state);
+        self.file.hash(state);
+        self.kind.hash(state);
+        self.visibility.hash(state);
+        self.scope.hash(state);
+    }
 }
 
 impl Symbol {
@@ -49,20 +87,81 @@ impl Symbol {
             None => "<invalid>".to_string(),
         })
     }
+
+    /// Part of the scope that does not come from the name of the file.
+    ///
+    /// ## Parameters:
+    /// * `language` (`&Languages`): Language of the file of the symbol.
+    ///
+    /// ## Returns:
+    /// * (`&[String]`): Scope of the symbol inside its file.
+    pub fn local_scope(&self, language: &Languages) -> &[String] {
+        let file_scope = language.scope_from_path(&self.file);
+        match self.scope.starts_with(&file_scope) {
+            true => &self.scope[file_scope.len()..],
+            false => &self.scope,
+        }
+    }
+
+    /// Scope of the symbol when named through its type, for methods of trait implementations
+    /// (eg. `X::method` for `method` in `impl Trait for X`).
+    ///
+    /// ## Returns:
+    /// * (`Option<Vec<String>>`): Scope without the trait, None if the symbol is not inside a
+    ///   trait implementation.
+    pub fn static_scope(&self) -> Option<Vec<String>> {
+        let mut found = false;
+        let scope = self
+            .scope
+            .iter()
+            .map(|scope_name| match split_trait_impl_scope(scope_name) {
+                Some((self_type, _)) => {
+                    found = true;
+                    self_type.to_string()
+                }
+                None => scope_name.clone(),
+            })
+            .collect();
+        found.then_some(scope)
+    }
+
+    /// Scope the symbol had before its file was renamed or moved.
+    ///
+    /// ## Parameters:
+    /// * `language` (`&Languages`): Language of the file of the symbol.
+    ///
+    /// ## Returns:
+    /// * (`Option<Vec<String>>`): Previous scope, None if the file was not renamed or moved.
+    pub fn previous_scope(&self, language: &Languages) -> Option<Vec<String>> {
+        let old_file = self.old_file.as_ref()?;
+        let mut scope = language.scope_from_path(old_file);
+        scope.extend(self.local_scope(language).iter().cloned());
+        Some(scope)
+    }
 }
 
 impl Display for Symbol {
     fn fmt(&self, formatter: &mut Formatter<'_>) -> std::fmt::Result {
         write!(
             formatter,
-            "{} {:?} \x1b[1m{}\x1b[0m ({}:{}): \x1b[2m{}\x1b[0m",
-            if self.is_exported {
-                "🔑public"
-            } else {
-                "🔒private"
+            "{} {:?}{} \x1b[1m{}\x1b[0m ({}{}:{}): \x1b[2m{}\x1b[0m",
+            match self.visibility {
+                Visibility::Public => "🔑public",
+                Visibility::Private => "🔒private",
+                _ => "🔐restricted",
             },
             self.kind,
+            match self.status {
+                SymbolStatus::Unchanged => "",
+                SymbolStatus::Added => " ➕added",
+                SymbolStatus::Modified => " ✏️modified",
+                SymbolStatus::Deleted => " 🗑️deleted",
+            },
             self.name(),
+            match &self.old_file {
+                Some(old_file) => format!("{} → ", old_file.to_str().unwrap_or("<invalid>")),
+                None => String::new(),
+            },
             self.file.to_str().unwrap_or("<invalid>"),
             self.line,
             self.scope.join("::"),
@@ -70,11 +169,80 @@ impl Display for Symbol {
     }
 }
 
+/// Scope name of the implementation of a trait for a type.
+///
+/// ## Parameters:
+/// * `self_type` (`&str`): Name of the implementing type,
+/// * `trait_name` (`&str`): Name of the implemented trait.
+///
+/// ## Returns:
+/// * (`String`): Scope name, as `<X as Trait>`.
+pub fn trait_impl_scope(self_type: &str, trait_name: &str) -> String {
+    format!("<{self_type} as {trait_name}>")
+}
+
+/// Splits the scope name of the implementation of a trait for a type.
+///
+/// ## Parameters:
+/// * `scope_name` (`&str`): Element of a scope.
+///
+/// ## Returns:
+/// * (`Option<(&str, &str)>`): Names of the implementing type and of the trait, None if the
+///   scope name is not a trait implementation.
+pub fn split_trait_impl_scope(scope_name: &str) -> Option<(&str, &str)> {
+    scope_name
+        .strip_prefix('<')?
+        .strip_suffix('>')?
+        .split_once(" as ")
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Symbol with the lines spanned by its definition.
+///
+/// ## Properties:
+/// * `symbol` (`Symbol`): Defined symbol,
+/// * `start_line` (`usize`): First line of the definition,
+/// * `end_line` (`usize`): Last line of the definition.
+pub struct Definition {
+    /// Defined symbol.
+    pub symbol: Symbol,
+    /// First line of the definition.
+    pub start_line: usize,
+    /// Last line of the definition.
+    pub end_line: usize,
+}
+
+impl Definition {
+    /// Returns true iff the definition spans the given line.
+    ///
+    /// ## Parameters:
+    /// * `line` (`usize`): Line number.
+    ///
+    /// ## Returns:
+    /// * (`bool`): true iff `line` is between the first and last lines of the definition.
+    pub fn contains(&self, line: usize) -> bool {
+        self.start_line <= line && line <= self.end_line
+    }
+
+    /// Returns true iff another definition is nested in this one.
+    ///
+    /// ## Parameters:
+    /// * `other` (`&Definition`): Definition of the same file.
+    ///
+    /// ## Returns:
+    /// * (`bool`): true iff `other` is a different definition spanning lines of this one.
+    pub fn strictly_contains(&self, other: &Definition) -> bool {
+        self.start_line <= other.start_line
+            && other.end_line <= self.end_line
+            && self.symbol != other.symbol
+    }
+}
+
 fn walk_tree<'a, F>(
     node: Node<'a>,
     file: &PathBuf,
     source: &str,
-    symbols: &mut Vec<Symbol>,
+    definitions: &mut Vec<Definition>,
     language: &Languages,
     scope_stack: &mut Vec<String>,
     get_name_and_kind_if_interesting: &F,
@@ -85,27 +253,38 @@ fn walk_tree<'a, F>(
     if let Some(ref scope_name) = new_scope {
         scope_stack.push(scope_name.to_string());
     }
-    if let Some((name_node, kind)) = get_name_and_kind_if_interesting(&node) {
-        let name = name_node
-            .utf8_text(source.as_bytes())
-            .unwrap_or("<unknown>")
-            .to_string();
+    let interesting = get_name_and_kind_if_interesting(&node);
+    if let Some((name_node, kind)) = interesting {
+        let name = match &new_scope {
+            Some(scope_name) => scope_name.clone(),
+            None => name_node
+                .utf8_text(source.as_bytes())
+                .unwrap_or("<unknown>")
+                .to_string(),
+        };
         let line = name_node.start_position().row + 1;
-        symbols.push(Symbol {
-            naming: Some(name),
-            line,
-            file: file.clone(),
-            kind: *kind,
-            is_exported: language.is_exported(node, source),
-            scope: scope_stack.clone(),
+        definitions.push(Definition {
+            symbol: Symbol {
+                naming: Some(name),
+                line,
+                file: file.clone(),
+                kind: *kind,
+                visibility: language.visibility(node, source),
+                scope: scope_stack.clone(),
+                status: SymbolStatus::Unchanged,
+                old_file: None,
+            },
+            start_line: language.definition_start(&node).start_position().row + 1,
+            end_line: node.end_position().row + 1,
         });
-    } else {
+    }
+    if interesting.is_none_or(|(_, kind)| kind.is_container()) {
         for child in node.children(&mut node.walk()) {
             walk_tree(
                 child,
                 file,
                 source,
-                symbols,
+                definitions,
                 language,
                 scope_stack,
                 get_name_and_kind_if_interesting,
@@ -117,7 +296,7 @@ fn walk_tree<'a, F>(
     }
 }
 
-/// Gets symbols from a parsed file contained in a node matching the given closure.
+/// Gets definitions from a parsed file contained in a node matching the given closure.
 ///
 /// ## Parameters:
 /// * `tree` (`&tree_sitter::Tree`): File parsed with tree_sitter,
@@ -129,20 +308,20 @@ fn walk_tree<'a, F>(
 ///   an interesting node, else node with the name of the given node, and kind of symbol extracted.
 ///
 /// ## Returns:
-/// * (`Result<Vec<Symbol>>`): List of symbol which matching the closure.
-pub fn extract_symbols<'a, F>(
+/// * (`Result<Vec<Definition>>`): List of definitions which matching the closure.
+pub fn extract_definitions<'a, F>(
     tree: &'a Tree,
     file: &std::path::PathBuf,
     source: &str,
     language: &Languages,
     add_path_to_scope: bool,
     get_name_and_kind_if_interesting: F,
-) -> Result<Vec<Symbol>>
+) -> Result<Vec<Definition>>
 where
     F: Fn(&Node<'a>) -> Option<(Node<'a>, &'static SymbolKind)>,
 {
     let cursor = tree.walk();
-    let mut symbols = Vec::new();
+    let mut definitions = Vec::new();
     let mut scope_stack = if add_path_to_scope {
         language.scope_from_path(file)
     } else {
@@ -152,16 +331,54 @@ where
         cursor.node(),
         file,
         source,
-        &mut symbols,
+        &mut definitions,
         language,
         &mut scope_stack,
         &get_name_and_kind_if_interesting,
     );
-    Ok(symbols)
+    Ok(definitions)
+}
+
+/// Gets symbols from a parsed file contained in a node matching the given closure.
+///
+/// ## Parameters:
+/// * `tree` (`&tree_sitter::Tree`): File parsed with tree_sitter,
+/// * `file` (`&std::path::PathBuf`): Name of the file,
+/// * `source` (`&str`): Content of the file,
+/// * `language` (`language::Languages`): Language of the current file,
+/// * `add_path_to_scope` (`bool`): True the scope of resulting symbols should begin with the path.
+/// * `get_name_and_kind_if_interesting` (`Fn(&Node) -> Option<(Node, &SymbolKind)>`): None if not
+///   an interesting node, else node with the name of the given node, and kind of symbol extracted.
+///
+/// ## Returns:
+/// * (`Result<Vec<Symbol>>`): List of symbol which matching the closure.
+pub fn extract_symbols<'a, F>(
+    tree: &'a Tree,
+    file: &std::path::PathBuf,
+    source: &str,
+    language: &Languages,
+    add_path_to_scope: bool,
+    get_name_and_kind_if_interesting: F,
+) -> Result<Vec<Symbol>>
+where
+    F: Fn(&Node<'a>) -> Option<(Node<'a>, &'static SymbolKind)>,
+{
+    let definitions = extract_definitions(
+        tree,
+        file,
+        source,
+        language,
+        add_path_to_scope,
+        get_name_and_kind_if_interesting,
+    )?;
+    Ok(definitions
+        .into_iter()
+        .map(|definition| definition.symbol)
+        .collect())
 }
 
 /// Gets changed symbols from a parsed file.
-/// Will extracts symbols present at line which changed.
+/// Will extracts symbols present at line which changed, attributes of a definition included.
 ///
 /// ## Parameters:
 /// * `tree` (`&tree_sitter::Tree`): File parsed with tree_sitter,
@@ -172,7 +389,7 @@ where
 ///
 /// ## Returns:
 /// * (`Result<Vec<Symbol>>`): List of symbol which changed. Will fail if the language was
-/// incorrect.
+///   incorrect.
 pub fn extract_changed_symbols<'a>(
     tree: &'a Tree,
     file: &PathBuf,
@@ -180,22 +397,160 @@ pub fn extract_changed_symbols<'a>(
     changed_lines: &HashSet<usize>,
     language: &Languages,
 ) -> Result<Vec<Symbol>> {
-    extract_symbols(
-        tree,
-        file,
-        source,
-        language,
-        true,
-        &(|node: &Node<'a>| {
+    let definitions =
+        extract_definitions(tree, file, source, language, true, |node: &Node<'a>| {
             if changed_lines.iter().any(|&changed_line| {
-                let starting_row = node.start_position().row;
-                let ending_row = node.end_position().row;
+                let starting_row = language.definition_start(node).start_position().row + 1;
+                let ending_row = node.end_position().row + 1;
                 (starting_row <= changed_line) && (changed_line <= ending_row)
             }) {
-                language.get_name_node_of_symbol(node)
+                language.get_name_node_of_symbol(node, source)
             } else {
                 None
             }
-        }),
-    )
+        })?;
+    // A container only changed if some changed lines are outside of the symbols it contains.
+    let is_changed = |definition: &Definition| {
+        !definition.symbol.kind.is_container()
+            || changed_lines.iter().any(|&line| {
+                definition.contains(line)
+                    && !definitions
+                        .iter()
+                        .any(|inner| definition.strictly_contains(inner) && inner.contains(line))
+            })
+    };
+    Ok(definitions
+        .iter()
+        .filter(|definition| is_changed(definition))
+        .map(|definition| definition.symbol.clone())
+        .collect())
+}
+
+/// Gets every symbol defined in a parsed file.
+///
+/// ## Parameters:
+/// * `tree` (`&tree_sitter::Tree`): File parsed with tree_sitter,
+/// * `file` (`&std::path::PathBuf`): Name of the file,
+/// * `source` (`&str`): Content of the file,
+/// * `language` (`language::Languages`): Language of the current file.
+///
+/// ## Returns:
+/// * (`Result<Vec<Symbol>>`): List of symbols defined in the file.
+pub fn extract_defined_symbols<'a>(
+    tree: &'a Tree,
+    file: &PathBuf,
+    source: &str,
+    language: &Languages,
+) -> Result<Vec<Symbol>> {
+    extract_symbols(tree, file, source, language, true, |node: &Node<'a>| {
+        language.get_name_node_of_symbol(node, source)
+    })
+}
+
+/// Version of a file on one side of a diff.
+///
+/// ## Properties:
+/// * `file` (`&std::path::PathBuf`): Name of the file in this version,
+/// * `source` (`&str`): Content of the file in this version,
+/// * `tree` (`&tree_sitter::Tree`): Content parsed with tree_sitter,
+/// * `changed_lines` (`&std::collections::HashSet<usize>`): Lines of this version touched by the
+///   diff.
+pub struct FileVersion<'a> {
+    /// Name of the file in this version.
+    pub file: &'a PathBuf,
+    /// Content of the file in this version.
+    pub source: &'a str,
+    /// Content parsed with tree_sitter.
+    pub tree: &'a Tree,
+    /// Lines of this version touched by the diff.
+    pub changed_lines: &'a HashSet<usize>,
+}
+
+/// Gets changed symbols from the old and new versions of a file.
+/// Symbols touched by added lines are `Added` or `Modified`, symbols touched by removed lines
+/// are `Modified` if they still exist in the new version, `Deleted` else.
+/// If the file was renamed or moved to another module, every symbol of the file changed path
+/// and is `Modified`, with `old_file` set to the previous name of the file.
+///
+/// ## Parameters:
+/// * `old` (`Option<&FileVersion>`): Version before the changes, None if the file was added,
+/// * `new` (`Option<&FileVersion>`): Version after the changes, None if the file was deleted,
+/// * `language` (`language::Languages`): Language of the file.
+///
+/// ## Returns:
+/// * (`Result<Vec<Symbol>>`): List of changed symbols, with their status.
+pub fn extract_symbols_changed_between(
+    old: Option<&FileVersion>,
+    new: Option<&FileVersion>,
+    language: &Languages,
+) -> Result<Vec<Symbol>> {
+    let old_symbols = match old {
+        Some(old) => extract_defined_symbols(old.tree, old.file, old.source, language)?,
+        None => Vec::new(),
+    };
+    let mut new_symbols = match new {
+        Some(new) => extract_defined_symbols(new.tree, new.file, new.source, language)?,
+        None => Vec::new(),
+    };
+    let old_file = old
+        .map(|old| old.file)
+        .filter(|&old_file| new.is_some_and(|new| new.file != old_file));
+    for symbol in new_symbols.iter_mut() {
+        symbol.old_file = old_file.cloned();
+    }
+    let existed_before = |symbol: &Symbol| {
+        old_symbols
+            .iter()
+            .any(|s| s.local_scope(language) == symbol.local_scope(language))
+    };
+    let mut changed: Vec<Symbol> = Vec::new();
+    if let Some(new) = new {
+        for mut symbol in
+            extract_changed_symbols(new.tree, new.file, new.source, new.changed_lines, language)?
+        {
+            symbol.old_file = old_file.cloned();
+            symbol.status = match existed_before(&symbol) {
+                true => SymbolStatus::Modified,
+                false => SymbolStatus::Added,
+            };
+            changed.push(symbol);
+        }
+    }
+    if let Some(old) = old {
+        for mut symbol in
+            extract_changed_symbols(old.tree, old.file, old.source, old.changed_lines, language)?
+        {
+            match new_symbols
+                .iter()
+                .find(|s| s.local_scope(language) == symbol.local_scope(language))
+            {
+                Some(new_symbol) => {
+                    if !changed.contains(new_symbol) {
+                        let mut new_symbol = new_symbol.clone();
+                        new_symbol.status = SymbolStatus::Modified;
+                        changed.push(new_symbol);
+                    }
+                }
+                None => {
+                    symbol.status = SymbolStatus::Deleted;
+                    changed.push(symbol);
+                }
+            }
+        }
+    }
+    let is_moved = new_symbols
+        .first()
+        .and_then(|symbol| symbol.previous_scope(language))
+        .is_some_and(|previous_scope| previous_scope != new_symbols[0].scope);
+    if is_moved {
+        for symbol in new_symbols {
+            if existed_before(&symbol) && !changed.contains(&symbol) {
+                changed.push(Symbol {
+                    status: SymbolStatus::Modified,
+                    ..symbol
+                });
+            }
+        }
+    }
+    Ok(changed)
 }
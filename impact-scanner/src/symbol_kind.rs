@@ -1,10 +1,45 @@
+use serde::{Deserialize, Serialize};
 use std::slice::Iter;
 
-#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+#[derive(PartialEq, Eq, Hash, Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 /// Kind of symbols to care about in source files.
 pub enum SymbolKind {
     /// Includes every named callable.
     Function,
+    /// Function run by the test harness (eg. `#[test]` or `#[tokio::test]`).
+    Test,
+    /// Structure, with named or unnamed fields.
+    Struct,
+    /// Enumeration.
+    Enum,
+    /// Union.
+    Union,
+    /// Alias of another type (eg. `type Result<T> = ...`).
+    TypeAlias,
+    /// Trait, containing its methods.
+    Trait,
+    /// Inherent implementation of a type (`impl X`), containing its methods.
+    Impl,
+    /// Implementation of a trait for a type (`impl Trait for X`), containing its methods.
+    TraitImpl,
+    /// Constant (`const`) outside of a trait or an implementation.
+    Const,
+    /// Static variable (`static`).
+    Static,
+    /// Constant declared in a trait or defined in an implementation.
+    AssocConst,
+    /// Type declared in a trait or defined in an implementation of a trait.
+    AssocType,
+    /// Named field of a structure, union or enumeration variant.
+    Field,
+    /// Variant of an enumeration, containing its named fields.
+    Variant,
+    /// Macro defined with `macro_rules!`, or macro invoked when considering usage.
+    Macro,
+    /// Method called on a value when considering usage, scoped under the type of the value when
+    /// it could be inferred.
+    Method,
     /// Generic symbol when considering usage
     Used,
 }
@@ -13,7 +48,58 @@ impl SymbolKind {
     ///
     /// ## Returns:
     /// - (`Iter<'static, SymbolKind>`): Iterator over all elements of the enum.
+    #[allow(dead_code)]
     pub fn iter() -> Iter<'static, SymbolKind> {
-        [SymbolKind::Function, SymbolKind::Used].iter()
+        [
+            SymbolKind::Function,
+            SymbolKind::Test,
+            SymbolKind::Struct,
+            SymbolKind::Enum,
+            SymbolKind::Union,
+            SymbolKind::TypeAlias,
+            SymbolKind::Trait,
+            SymbolKind::Impl,
+            SymbolKind::TraitImpl,
+            SymbolKind::Const,
+            SymbolKind::Static,
+            SymbolKind::AssocConst,
+            SymbolKind::AssocType,
+            SymbolKind::Field,
+            SymbolKind::Variant,
+            SymbolKind::Macro,
+            SymbolKind::Method,
+            SymbolKind::Used,
+        ]
+        .iter()
+    }
+
+    /// Returns true iff symbols of this kind contain other symbols, eg. the methods of a trait or
+    /// the fields of a structure.
+    ///
+    /// ## Returns:
+    /// - (`bool`): true iff the definition of the symbol may contain other definitions.
+    pub fn is_container(&self) -> bool {
+        matches!(
+            self,
+            SymbolKind::Trait
+                | SymbolKind::Impl
+                | SymbolKind::TraitImpl
+                | SymbolKind::Struct
+                | SymbolKind::Enum
+                | SymbolKind::Union
+                | SymbolKind::Variant
+        )
+    }
+
+    /// Returns true iff symbols of this kind can be items of a trait, implemented separately for
+    /// each type.
+    ///
+    /// ## Returns:
+    /// - (`bool`): true iff the symbol is a function, an associated constant or type.
+    pub fn is_associated(&self) -> bool {
+        matches!(
+            self,
+            SymbolKind::Function | SymbolKind::AssocConst | SymbolKind::AssocType
+        )
     }
 }
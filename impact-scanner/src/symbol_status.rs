@@ -0,0 +1,16 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(PartialEq, Eq, Hash, Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+/// How a symbol was affected by the analyzed changes.
+pub enum SymbolStatus {
+    /// Not changed itself, eg. a symbol using a changed symbol.
+    #[default]
+    Unchanged,
+    /// Did not exist before the changes.
+    Added,
+    /// Existed before the changes, and its definition changed.
+    Modified,
+    /// Existed before the changes, and no longer exists.
+    Deleted,
+}
@@ -0,0 +1,71 @@
+use serde::Serialize;
+use std::collections::HashSet;
+
+use crate::{
+    impact::ImpactGraph, symbol::Symbol, symbol_kind::SymbolKind, symbol_status::SymbolStatus,
+    workspace::Workspace,
+};
+
+#[derive(Debug, Clone, Serialize)]
+/// Test function affected by the changes.
+///
+/// ## Properties:
+/// * `symbol` (`Symbol`): Test function,
+/// * `filter` (`String`): Name of the test as accepted by `cargo test` as a filter,
+/// * `package` (`Option<String>`): Package containing the test, None if it is outside of every
+///   package of the workspace.
+pub struct SelectedTest {
+    /// Test function.
+    pub symbol: Symbol,
+    /// Name of the test as accepted by `cargo test` as a filter.
+    pub filter: String,
+    /// Package containing the test, None if it is outside of every package of the workspace.
+    pub package: Option<String>,
+}
+
+/// Name of a test function, as displayed and filtered by the test harness.
+/// The leading `crate` is removed.
+///
+/// ## Parameters:
+/// * `symbol` (`&Symbol`): Test function.
+///
+/// ## Returns:
+/// * (`String`): Path of the test inside its test binary (eg. `module::tests::test_name`).
+pub fn test_filter(symbol: &Symbol) -> String {
+    symbol
+        .scope
+        .iter()
+        .skip(1)
+        .cloned()
+        .collect::<Vec<String>>()
+        .join("::")
+}
+
+/// Selects the test functions that are changed or affected by the changes.
+/// Deleted tests can not be run and are ignored.
+///
+/// ## Parameters:
+/// * `graph` (`&ImpactGraph`): Result of the impact analysis,
+/// * `workspace` (`&Workspace`): Packages of the project.
+///
+/// ## Returns:
+/// * (`Vec<SelectedTest>`): Affected tests, sorted by package and filter, without duplicates.
+pub fn select_tests(graph: &ImpactGraph, workspace: &Workspace) -> Vec<SelectedTest> {
+    let mut seen = HashSet::new();
+    let mut tests: Vec<SelectedTest> = graph
+        .changed
+        .iter()
+        .chain(graph.affected())
+        .filter(|symbol| symbol.kind == SymbolKind::Test && symbol.status != SymbolStatus::Deleted)
+        .map(|symbol| SelectedTest {
+            symbol: symbol.clone(),
+            filter: test_filter(symbol),
+            package: workspace
+                .package_of(&symbol.file)
+                .map(|package| package.name.clone()),
+        })
+        .filter(|test| seen.insert((test.package.clone(), test.filter.clone())))
+        .collect();
+    tests.sort_by(|a, b| (&a.package, &a.filter).cmp(&(&b.package, &b.filter)));
+    tests
+}
@@ -1,17 +1,23 @@
 use anyhow::{self, bail, Result};
 use std::collections::HashSet;
-use std::fs;
-use std::{collections::HashMap, path::PathBuf};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
 
+use crate::index::SymbolIndex;
+use crate::locals::Locals;
 use crate::symbol_kind::SymbolKind;
+use crate::symbol_status::SymbolStatus;
+use crate::visibility::Visibility;
 use crate::{
     language::{parsable_language::ParsableLanguage, Languages},
     symbol::Symbol,
 };
+use serde::Serialize;
 use tree_sitter::Node;
-use walkdir::WalkDir;
 
-#[derive(Debug, Eq, Hash, PartialEq)]
+#[derive(Debug, Clone, Eq, Hash, PartialEq, Serialize)]
 /// Usage of a symbol in a project.
 ///
 /// ## Properties:
@@ -34,7 +40,7 @@ pub struct Usage {
 //),
 fn process_generic_type_with_turbofish(
     node: Node,
-    path: &PathBuf,
+    path: &Path,
     source: &str,
     language: &Languages,
     imported_symbols: &mut Vec<Symbol>,
@@ -51,6 +57,16 @@ fn process_generic_type_with_turbofish(
     }
 }
 
+//scoped_type_identifier: $ => seq(
+//  field('path', optional(choice(
+//    $._path,
+//    alias($.generic_type_with_turbofish, $.generic_type),
+//    $.bracketed_type,
+//    $.generic_type,
+//  ))),
+//  '::',
+//  field('name', $._type_identifier),
+//),
 //scoped_identifier: $ => seq(
 //  field('path', optional(choice(
 //    $._path,
@@ -62,7 +78,7 @@ fn process_generic_type_with_turbofish(
 //),
 fn process_scoped_identifier(
     node: Node,
-    path: &PathBuf,
+    path: &Path,
     source: &str,
     language: &Languages,
     imported_symbols: &mut Vec<Symbol>,
@@ -71,14 +87,16 @@ fn process_scoped_identifier(
         match path_node.kind() {
             "self" => (),
             "metavariable" => process_metavariable(path_node, source, imported_symbols)?,
-            "super" => process_super(path, language, imported_symbols)?,
+            "super" => process_super(node, path, source, language, imported_symbols)?,
             "crate" => process_crate(imported_symbols)?,
             "identifier" => process_identifier(path_node, source, imported_symbols)?,
             "scoped_identifier" => {
                 process_scoped_identifier(path_node, path, source, language, imported_symbols)?
             }
             "_reserved_identifier" => process_identifier(path_node, source, imported_symbols)?,
-            "bracketed_type" => (),
+            "bracketed_type" => {
+                process_bracketed_type(path_node, path, source, language, imported_symbols)?
+            }
             "generic_type" => process_generic_type_with_turbofish(
                 path_node,
                 path,
@@ -93,12 +111,52 @@ fn process_scoped_identifier(
         bail!("field name `name` not found for a scoped identifier")
     };
     match name_node.kind() {
-        "identifier" => process_identifier(name_node, source, imported_symbols),
-        "super" => process_super(path, language, imported_symbols),
+        "identifier" | "type_identifier" => process_identifier(name_node, source, imported_symbols),
+        "super" => process_super(node, path, source, language, imported_symbols),
         _ => bail!("name node of a scoped identifier has invalid kind"),
     }
 }
 
+// The type of a qualified path is kept, since items of trait implementations can also be named
+// through their type.
+//bracketed_type: $ => seq(
+//  '<',
+//  choice(
+//    $._type,
+//    $.qualified_type,
+//  ),
+//  '>',
+//),
+//qualified_type: $ => seq(
+//  field('type', $._type),
+//  'as',
+//  field('alias', $._type),
+//),
+fn process_bracketed_type(
+    node: Node,
+    path: &Path,
+    source: &str,
+    language: &Languages,
+    imported_symbols: &mut Vec<Symbol>,
+) -> Result<()> {
+    let Some(mut type_node) = node.named_child(0) else {
+        bail!("type not found for a bracketed type")
+    };
+    if type_node.kind() == "qualified_type" {
+        let Some(qualified) = type_node.child_by_field_name("type") else {
+            bail!("field name `type` not found for a qualified type")
+        };
+        type_node = qualified;
+    }
+    match type_node.kind() {
+        "type_identifier" => process_identifier(type_node, source, imported_symbols),
+        "scoped_type_identifier" => {
+            process_scoped_identifier(type_node, path, source, language, imported_symbols)
+        }
+        _ => Ok(()),
+    }
+}
+
 //crate: _ => 'crate',
 fn process_crate(imported_symbols: &mut Vec<Symbol>) -> Result<()> {
     for imported_symbol in imported_symbols {
@@ -107,13 +165,42 @@ fn process_crate(imported_symbols: &mut Vec<Symbol>) -> Result<()> {
     Ok(())
 }
 
+/// Scope of the module containing a node: the scope of its file, followed by the inline modules
+/// around the node.
+///
+/// ## Parameters:
+/// * `node` (`tree_sitter::Node`): Node of the file,
+/// * `path` (`&std::path::Path`): Name of the file,
+/// * `source` (`&str`): Content of the file,
+/// * `language` (`&Languages`): Language of the file.
+///
+/// ## Returns:
+/// * (`Vec<String>`): Scope of the innermost module containing the node.
+fn module_scope(node: Node, path: &Path, source: &str, language: &Languages) -> Vec<String> {
+    let mut inline_modules = Vec::new();
+    let mut ancestor = node.parent();
+    while let Some(current) = ancestor {
+        if current.kind() == "mod_item" {
+            if let Some(name) = current.child_by_field_name("name") {
+                inline_modules.push(get_value_of_identifier(name, source).unwrap_or_default());
+            }
+        }
+        ancestor = current.parent();
+    }
+    let mut scope = language.scope_from_path(path);
+    scope.extend(inline_modules.into_iter().rev());
+    scope
+}
+
 //super: _ => 'super',
 fn process_super(
-    path: &PathBuf,
+    node: Node,
+    path: &Path,
+    source: &str,
     language: &Languages,
     imported_symbols: &mut Vec<Symbol>,
 ) -> Result<()> {
-    let mut from_path = language.scope_from_path(path);
+    let mut from_path = module_scope(node, path, source, language);
     from_path.pop();
     for imported_symbol in imported_symbols {
         imported_symbol.scope.extend(from_path.clone());
@@ -122,11 +209,7 @@ fn process_super(
 }
 
 //  metavariable: _ => /\$[a-zA-Z_]\w*/,
-fn process_metavariable(
-    node: Node,
-    source: &str,
-    imported_symbols: &mut Vec<Symbol>,
-) -> Result<()> {
+fn process_metavariable(node: Node, source: &str, imported_symbols: &mut [Symbol]) -> Result<()> {
     let value = node.utf8_text(source.as_bytes()).map(|v| v.to_string())?;
     for imported_symbol in imported_symbols.iter_mut() {
         imported_symbol.scope.push(value.clone());
@@ -146,7 +229,7 @@ fn process_metavariable(
 //  ),
 fn process_path(
     node: Node,
-    path: &PathBuf,
+    path: &Path,
     source: &str,
     language: &Languages,
     imported_symbols: &mut Vec<Symbol>,
@@ -154,7 +237,7 @@ fn process_path(
     match node.kind() {
         "self" => Ok(()),
         "metavariable" => process_metavariable(node, source, imported_symbols),
-        "super" => process_super(path, language, imported_symbols),
+        "super" => process_super(node, path, source, language, imported_symbols),
         "crate" => process_crate(imported_symbols),
         "identifier" => process_identifier(node, source, imported_symbols),
         "scoped_identifier" => {
@@ -186,7 +269,7 @@ fn get_value_of_identifier(node: Node, source: &str) -> Result<String> {
 //   ),
 fn process_use_wildcard(
     node: Node,
-    path: &PathBuf,
+    path: &Path,
     source: &str,
     language: &Languages,
     imported_symbols: &mut Vec<Symbol>,
@@ -210,7 +293,7 @@ fn process_use_wildcard(
 //   ),
 fn process_use_as_clause(
     node: Node,
-    path: &PathBuf,
+    path: &Path,
     source: &str,
     language: &Languages,
     imported_symbols: &mut Vec<Symbol>,
@@ -236,7 +319,7 @@ fn process_use_as_clause(
 //   ),
 fn process_scoped_use_list(
     node: Node,
-    path: &PathBuf,
+    path: &Path,
     source: &str,
     language: &Languages,
     imported_symbols: &mut Vec<Symbol>,
@@ -260,18 +343,18 @@ fn process_scoped_use_list(
 //   ),
 fn process_use_list(
     node: Node,
-    path: &PathBuf,
+    path: &Path,
     source: &str,
     language: &Languages,
     imported_symbols: &mut Vec<Symbol>,
 ) -> Result<()> {
     let mut cursor = node.walk();
-    let cloned: Vec<Symbol> = imported_symbols.iter().cloned().collect();
+    let cloned: Vec<Symbol> = imported_symbols.to_vec();
     imported_symbols.clear();
     for child in node.children(&mut cursor) {
         let kind = child.kind();
         if kind != "{" && kind != "}" && kind != "," {
-            let mut imported_symbols_part = cloned.iter().cloned().collect();
+            let mut imported_symbols_part = cloned.to_vec();
             process_use_clause(child, path, source, language, &mut imported_symbols_part)?;
             imported_symbols.extend(imported_symbols_part);
         }
@@ -288,7 +371,7 @@ fn process_use_list(
 //   ),
 fn process_use_clause(
     node: Node,
-    path: &PathBuf,
+    path: &Path,
     source: &str,
     language: &Languages,
     imported_symbols: &mut Vec<Symbol>,
@@ -296,7 +379,7 @@ fn process_use_clause(
     match node.kind() {
         "self" => Ok(()),
         "metavariable" => process_metavariable(node, source, imported_symbols),
-        "super" => process_super(path, language, imported_symbols),
+        "super" => process_super(node, path, source, language, imported_symbols),
         "crate" => process_crate(imported_symbols),
         "identifier" => process_identifier(node, source, imported_symbols),
         "scoped_identifier" => {
@@ -324,26 +407,25 @@ fn process_use_clause(
 //),
 fn process_mod_item(
     node: Node,
-    path: &PathBuf,
+    path: &Path,
     source: &str,
     language: &Languages,
 ) -> Result<Vec<Symbol>> {
-    let is_exported = node
-        .child(0)
-        .map(|arg| arg.kind() == "visibility_modifier")
-        .unwrap_or(false);
+    let visibility = language.visibility(node, source);
     let Some(identifier) = node.child_by_field_name("name") else {
         bail!("field name `name` not found for a mod item")
     };
-    let mut from_path = language.scope_from_path(path);
+    let mut from_path = module_scope(node, path, source, language);
     from_path.push(get_value_of_identifier(identifier, source)?);
     Ok(vec![Symbol {
         naming: None,
         file: path.to_path_buf(),
         kind: SymbolKind::Used,
         scope: from_path,
-        is_exported,
+        visibility: visibility.clone(),
         line: node.start_position().row + 1,
+        status: SymbolStatus::Unchanged,
+        old_file: None,
     }])
 }
 
@@ -355,14 +437,11 @@ fn process_mod_item(
 //   ),
 fn process_use_declaration(
     node: Node,
-    path: &PathBuf,
+    path: &Path,
     source: &str,
     language: &Languages,
 ) -> Result<Vec<Symbol>> {
-    let is_exported = node
-        .child(0)
-        .map(|arg| arg.kind() == "visibility_modifier")
-        .unwrap_or(false);
+    let visibility = language.visibility(node, source);
     let Some(argument) = node.child_by_field_name("argument") else {
         bail!("field name `argument` not found for a use declaration")
     };
@@ -371,23 +450,43 @@ fn process_use_declaration(
         file: path.to_path_buf(),
         kind: SymbolKind::Used,
         scope: Vec::new(),
-        is_exported,
+        visibility: visibility.clone(),
         line: node.start_position().row + 1,
+        status: SymbolStatus::Unchanged,
+        old_file: None,
     }];
     process_use_clause(argument, path, source, language, &mut imported_symbols)?;
     Ok(imported_symbols)
 }
 
+/// Extracts the symbols imported in a file, by `use` declarations or declared modules.
+///
+/// ## Parameters:
+/// * `node` (`tree_sitter::Node`): Node to extract the imports of, with its descendants,
+/// * `path` (`&std::path::Path`): Name of the file,
+/// * `source` (`&str`): Content of the file,
+/// * `use_map` (`&mut HashMap<String, Symbol>`): Explicitly imported symbols, by name,
+/// * `globs` (`&mut Vec<Symbol>`): Modules whose items are all imported (`use module::*`),
+/// * `language` (`&Languages`): Language of the file.
+///
+/// ## Returns:
+/// * (`Result<()>`): Error if an import could not be parsed.
 pub fn extract_use_map(
     node: Node,
-    path: &PathBuf,
+    path: &Path,
     source: &str,
     use_map: &mut HashMap<String, Symbol>,
+    globs: &mut Vec<Symbol>,
     language: &Languages,
 ) -> Result<()> {
     if node.kind() == "use_declaration" {
-        for imported_symbol in process_use_declaration(node, path, source, language)? {
-            use_map.insert(imported_symbol.name(), imported_symbol);
+        for mut imported_symbol in process_use_declaration(node, path, source, language)? {
+            if imported_symbol.scope.last().is_some_and(|name| name == "*") {
+                imported_symbol.scope.pop();
+                globs.push(imported_symbol);
+            } else {
+                use_map.insert(imported_symbol.name(), imported_symbol);
+            }
         }
     } else if node.kind() == "mod_item" {
         for imported_symbol in process_mod_item(node, path, source, language)? {
@@ -395,125 +494,635 @@ pub fn extract_use_map(
         }
     }
     for child in node.named_children(&mut node.walk()) {
-        extract_use_map(child, path, source, use_map, language)?;
+        extract_use_map(child, path, source, use_map, globs, language)?;
     }
     Ok(())
 }
 
+/// Creates the symbol used at a node, before its scope is known.
+///
+/// ## Parameters:
+/// * `node` (`tree_sitter::Node`): Node naming the symbol,
+/// * `path` (`&std::path::Path`): Name of the file,
+/// * `kind` (`SymbolKind`): `Macro` for an invoked macro, `Field` or `Method` for a member of a
+///   value, else `Used`.
+///
+/// ## Returns:
+/// * (`Symbol`): Used symbol with an empty scope.
+fn used_symbol(node: Node, path: &Path, kind: SymbolKind) -> Symbol {
+    Symbol {
+        naming: None,
+        file: path.to_path_buf(),
+        kind,
+        scope: Vec::new(),
+        visibility: Visibility::Private,
+        line: node.start_position().row + 1,
+        status: SymbolStatus::Unchanged,
+        old_file: None,
+    }
+}
+
+//macro_invocation: $ => seq(
+//  field('macro', choice(
+//    $.scoped_identifier,
+//    $.identifier,
+//    $._reserved_identifier,
+//  )),
+//  '!',
+//  alias($.delim_token_tree, $.token_tree),
+//),
+fn process_macro_invocation(
+    node: Node,
+    path: &Path,
+    source: &str,
+    language: &Languages,
+    locals: &Locals,
+) -> Result<Vec<Symbol>> {
+    let Some(macro_node) = node.child_by_field_name("macro") else {
+        bail!("field name `macro` not found for a macro invocation")
+    };
+    let mut symbol = vec![used_symbol(macro_node, path, SymbolKind::Macro)];
+    match macro_node.kind() {
+        "scoped_identifier" => {
+            process_scoped_identifier(macro_node, path, source, language, &mut symbol)?
+        }
+        _ => process_identifier(macro_node, source, &mut symbol)?,
+    }
+    let mut result = symbol;
+    for child in node.named_children(&mut node.walk()) {
+        if child.kind() == "token_tree" {
+            result.extend(process_token_tree(child, path, source, language, locals)?);
+        }
+    }
+    Ok(result)
+}
+
+// Token trees are flat sequences of tokens: paths are rebuilt from identifiers separated by
+// `::`, and a path followed by `!` is a macro invocation. Identifiers following a `.` are fields,
+// or methods when called, and metavariables other than `$crate` are unknown. Local bindings
+// are left out.
+//token_tree: $ => choice(
+//  seq('(', repeat($._tokens), ')'),
+//  seq('[', repeat($._tokens), ']'),
+//  seq('{', repeat($._tokens), '}'),
+//),
+fn process_token_tree(
+    node: Node,
+    path: &Path,
+    source: &str,
+    language: &Languages,
+    locals: &Locals,
+) -> Result<Vec<Symbol>> {
+    let mut result = Vec::new();
+    let mut current: Option<Vec<Symbol>> = None;
+    let mut after_separator = false;
+    let mut after_dot = false;
+    for child in node.children(&mut node.walk()) {
+        let is_segment = matches!(child.kind(), "identifier" | "crate" | "super" | "self")
+            || (child.kind() == "metavariable" && child.utf8_text(source.as_bytes())? == "$crate");
+        if !is_segment {
+            match child.kind() {
+                "::" if current.is_some() => after_separator = true,
+                "!" if !after_separator => {
+                    for symbol in current.iter_mut().flatten() {
+                        symbol.kind = SymbolKind::Macro;
+                    }
+                }
+                _ => {
+                    result.extend(current.take().into_iter().flatten());
+                    after_separator = false;
+                    after_dot = child.kind() == ".";
+                    if child.kind() == "token_tree" {
+                        result.extend(process_token_tree(child, path, source, language, locals)?);
+                    }
+                }
+            }
+            continue;
+        }
+        if locals.identifiers.contains(&child.start_byte()) {
+            result.extend(current.take().into_iter().flatten());
+            after_separator = false;
+            continue;
+        }
+        if after_dot {
+            after_dot = false;
+            let is_method = child
+                .next_sibling()
+                .is_some_and(|next| next.kind() == "token_tree");
+            if child.kind() == "identifier" {
+                let kind = match is_method {
+                    true => SymbolKind::Method,
+                    false => SymbolKind::Field,
+                };
+                result.extend(member_symbol(child, path, source, kind, locals)?);
+            }
+            continue;
+        }
+        if !after_separator {
+            result.extend(current.take().into_iter().flatten());
+        }
+        after_separator = false;
+        let symbols =
+            current.get_or_insert_with(|| vec![used_symbol(child, path, SymbolKind::Used)]);
+        match child.kind() {
+            "self" => (),
+            "super" => process_super(child, path, source, language, symbols)?,
+            "crate" | "metavariable" => process_crate(symbols)?,
+            _ => process_identifier(child, source, symbols)?,
+        }
+    }
+    result.extend(current.into_iter().flatten());
+    Ok(result
+        .into_iter()
+        .filter(|symbol| !symbol.scope.is_empty())
+        .collect())
+}
+
+// A field accessed or a method called on a value is scoped under the type of the value when it
+// could be inferred, else only known by its name.
+//field_expression: $ => prec(PREC.field, seq(
+//  field('value', $._expression),
+//  '.',
+//  field('field', choice(
+//    $._field_identifier,
+//    $.integer_literal,
+//  )),
+//)),
+fn process_field_expression(
+    node: Node,
+    path: &Path,
+    source: &str,
+    locals: &Locals,
+) -> Result<Vec<Symbol>> {
+    let Some(field_node) = node.child_by_field_name("field") else {
+        bail!("field name `field` not found for a field expression")
+    };
+    if field_node.kind() != "field_identifier" {
+        return Ok(Vec::new());
+    }
+    let is_method = node.parent().is_some_and(|parent| {
+        parent.kind() == "call_expression" && parent.child_by_field_name("function") == Some(node)
+    });
+    let kind = match is_method {
+        true => SymbolKind::Method,
+        false => SymbolKind::Field,
+    };
+    member_symbol(field_node, path, source, kind, locals)
+}
+
+/// Creates the symbol of a field accessed or a method called on a value.
+///
+/// ## Parameters:
+/// * `node` (`tree_sitter::Node`): Node naming the field or the method,
+/// * `path` (`&std::path::Path`): Name of the file,
+/// * `source` (`&str`): Content of the file,
+/// * `kind` (`SymbolKind`): `Field` or `Method`,
+/// * `locals` (`&Locals`): Local bindings of the file, and types of the receivers of fields and
+///   methods.
+///
+/// ## Returns:
+/// * (`Result<Vec<Symbol>>`): Used symbol, scoped under the type of its receiver when it is
+///   known.
+fn member_symbol(
+    node: Node,
+    path: &Path,
+    source: &str,
+    kind: SymbolKind,
+    locals: &Locals,
+) -> Result<Vec<Symbol>> {
+    let mut symbol = vec![used_symbol(node, path, kind)];
+    if let Some(receiver) = locals.receivers.get(&node.start_byte()) {
+        symbol[0].scope.extend(receiver.iter().cloned());
+    }
+    process_identifier(node, source, &mut symbol)?;
+    Ok(symbol)
+}
+
+// Fields named in a struct expression or pattern are scoped under the named type.
+//field_initializer: $ => seq(
+//  repeat($.attribute_item),
+//  field('field', choice($._field_identifier, $.integer_literal)),
+//  ':',
+//  field('value', $._expression),
+//),
+//shorthand_field_initializer: $ => seq(
+//  repeat($.attribute_item),
+//  $.identifier,
+//),
+//field_pattern: $ => seq(
+//  optional('ref'),
+//  optional($.mutable_specifier),
+//  choice(
+//    field('name', alias($.identifier, $.shorthand_field_identifier)),
+//    seq(
+//      field('name', $._field_identifier),
+//      ':',
+//      field('pattern', $._pattern),
+//    ),
+//  ),
+//),
+fn process_struct_field(
+    node: Node,
+    path: &Path,
+    source: &str,
+    language: &Languages,
+) -> Result<Vec<Symbol>> {
+    let field_node = match node.kind() {
+        "field_initializer" => node.child_by_field_name("field"),
+        "field_pattern" => node.child_by_field_name("name"),
+        _ => node
+            .named_children(&mut node.walk())
+            .find(|child| child.kind() == "identifier"),
+    };
+    let Some(field_node) = field_node.filter(|field| field.kind() != "integer_literal") else {
+        return Ok(Vec::new());
+    };
+    // Struct expressions name their type in `name`, struct patterns in `type`.
+    let type_node = node.parent().and_then(|list| match list.kind() {
+        "field_initializer_list" => list.parent()?.child_by_field_name("name"),
+        _ => list.child_by_field_name("type"),
+    });
+    let mut symbol = vec![used_symbol(field_node, path, SymbolKind::Field)];
+    if let Some(mut type_node) = type_node {
+        if type_node.kind() == "generic_type" {
+            let Some(generic) = type_node.child_by_field_name("type") else {
+                bail!("field name `type` not found for a generic type")
+            };
+            type_node = generic;
+        }
+        match type_node.kind() {
+            "type_identifier" | "identifier" => process_identifier(type_node, source, &mut symbol)?,
+            "scoped_type_identifier" | "scoped_identifier" => {
+                process_scoped_identifier(type_node, path, source, language, &mut symbol)?
+            }
+            _ => (),
+        }
+    }
+    process_identifier(field_node, source, &mut symbol)?;
+    Ok(symbol)
+}
+
+/// Returns true iff a node is the name of the symbol defined by its parent.
+/// Implementations are named after their type, which they use.
+///
+/// ## Parameters:
+/// * `node` (`tree_sitter::Node`): Identifier,
+/// * `source` (`&str`): Content of the file,
+/// * `language` (`&Languages`): Language of the file.
+///
+/// ## Returns:
+/// * (`bool`): true iff the identifier names a definition.
+fn is_definition_name(node: Node, source: &str, language: &Languages) -> bool {
+    node.parent().is_some_and(|parent| {
+        parent.kind() != "impl_item"
+            && language
+                .get_name_node_of_symbol(&parent, source)
+                .is_some_and(|(name_node, _)| name_node == node)
+    })
+}
+
+/// Extracts the symbols used in a node and its descendants.
+/// Identifiers designating local bindings are left out, as they never refer to other symbols,
+/// and so are the names of definitions, which do not use the symbols they define.
+/// Methods are scoped under the type of their receiver when it is known.
+///
+/// ## Parameters:
+/// * `node` (`tree_sitter::Node`): Node to extract the used symbols from,
+/// * `path` (`&Path`): Path of the file,
+/// * `source` (`&str`): Content of the file,
+/// * `language` (`&Languages`): Language of the file,
+/// * `locals` (`&Locals`): Local bindings of the file, and types of the receivers of fields and
+///   methods.
+///
+/// ## Returns:
+/// * (`Result<Vec<Symbol>>`): Used symbols, not resolved yet.
 pub fn extract_identifiers(
     node: Node,
-    path: &PathBuf,
+    path: &Path,
     source: &str,
     language: &Languages,
+    locals: &Locals,
 ) -> Result<Vec<Symbol>> {
     let mut result = Vec::new();
     let mut processed = false;
-    if node.kind() == "scoped_identifier" {
-        let mut symbol = vec![Symbol {
-            naming: None,
-            file: path.to_path_buf(),
-            kind: SymbolKind::Used,
-            scope: Vec::new(),
-            is_exported: false,
-            line: node.start_position().row + 1,
-        }];
+    match node.kind() {
+        "macro_invocation" => {
+            return process_macro_invocation(node, path, source, language, locals);
+        }
+        "token_tree" => return process_token_tree(node, path, source, language, locals),
+        "identifier" if locals.identifiers.contains(&node.start_byte()) => return Ok(result),
+        "identifier" | "type_identifier" if is_definition_name(node, source, language) => {
+            return Ok(result)
+        }
+        "token_tree_pattern" => return Ok(result),
+        "field_expression" => result.extend(process_field_expression(node, path, source, locals)?),
+        "field_initializer" | "shorthand_field_initializer" | "field_pattern" => {
+            result.extend(process_struct_field(node, path, source, language)?);
+        }
+        _ => (),
+    }
+    if node.kind() == "scoped_identifier" || node.kind() == "scoped_type_identifier" {
+        let mut symbol = vec![used_symbol(node, path, SymbolKind::Used)];
         process_scoped_identifier(node, path, source, language, &mut symbol)?;
         result.extend(symbol);
         processed = true;
     }
-    if node.kind() == "identifier" {
-        let mut symbol = vec![Symbol {
-            naming: None,
-            file: path.to_path_buf(),
-            kind: SymbolKind::Used,
-            scope: Vec::new(),
-            is_exported: false,
-            line: node.start_position().row + 1,
-        }];
+    if node.kind() == "identifier" || node.kind() == "type_identifier" {
+        let mut symbol = vec![used_symbol(node, path, SymbolKind::Used)];
         process_identifier(node, source, &mut symbol)?;
         result.extend(symbol);
         processed = true;
     }
     if !processed {
         for child in node.named_children(&mut node.walk()) {
-            result.extend(extract_identifiers(child, path, source, language)?);
+            result.extend(extract_identifiers(child, path, source, language, locals)?);
         }
     }
     Ok(result)
 }
 
+/// Replaces the first segment of a scope by the path it was imported from, as long as it was.
+///
+/// ## Parameters:
+/// * `scope` (`&mut Vec<String>`): Scope to resolve,
+/// * `use_map` (`&HashMap<String, Symbol>`): Symbols imported in the current file, by name.
+pub fn resolve_with_use_map(scope: &mut Vec<String>, use_map: &HashMap<String, Symbol>) {
+    let mut seen = HashSet::new();
+    while let Some(imported_symbol) = scope.first().and_then(|v| use_map.get(v)) {
+        if !seen.insert(imported_symbol.scope.clone()) {
+            break;
+        }
+        scope.splice(0..1, imported_symbol.scope.iter().cloned());
+    }
+}
+
+/// Finds every place of the project where the given symbol is used.
+/// Only the package of the symbol and the packages depending on it are scanned, and only the
+/// modules able to see the symbol when it is not public.
+/// If the file of the symbol was renamed or moved, usages of its previous path are included.
+/// Methods of trait implementations are also looked for through their type (eg. `X::method`),
+/// and macros through their invocations. Paths going through re-exports are followed up to the
+/// definition, and paths that are not imported explicitly are looked for in glob imports.
+/// Fields and methods accessed on a value are matched through the type inferred for the value,
+/// and by name only when the type is unknown or not defined in the project, unless
+/// `strict_methods` is set for methods.
+///
+/// ## Parameters:
+/// * `index` (`&SymbolIndex`): Index of the project to search,
+/// * `symbol` (`&Symbol`): Symbol to look for,
+/// * `language` (`&Languages`): Language of the file of the symbol,
+/// * `strict_methods` (`bool`): true to ignore method calls on values of unknown type.
+///
+/// ## Returns:
+/// * (`HashSet<Usage>`): Places where the symbol is used. Files are relative to the project root.
 pub fn find_symbol_usages(
-    project_root: &PathBuf,
+    index: &SymbolIndex,
     symbol: &Symbol,
     language: &Languages,
+    strict_methods: bool,
 ) -> HashSet<Usage> {
-    eprintln!("DEBUGPRINT[68]: usage.rs:367: symbol={:#?}", symbol);
     let mut usages: HashSet<Usage> = HashSet::new();
-
-    for entry in WalkDir::new(project_root)
+    if matches!(symbol.kind, SymbolKind::Impl | SymbolKind::TraitImpl) {
+        // Implementations are never named, their methods and their type are.
+        return usages;
+    }
+    let workspace = &index.workspace;
+    let symbol_scope = workspace.absolute_scope(&symbol.file, &symbol.scope);
+    let previous_scope = symbol
+        .previous_scope(language)
+        .zip(symbol.old_file.as_ref())
+        .map(|(scope, old_file)| workspace.absolute_scope(old_file, &scope));
+    let static_scope = symbol
+        .static_scope()
+        .map(|scope| workspace.absolute_scope(&symbol.file, &scope));
+    let exported_macro_scope = (symbol.kind == SymbolKind::Macro && symbol.visibility.is_public())
+        .then(|| workspace.absolute_scope(&symbol.file, &["crate".to_string(), symbol.name()]));
+    let name = symbol.name();
+    let static_name = static_scope.as_ref().and_then(|scope| scope.last());
+    let scanned_packages = workspace.packages_using(&symbol.file, symbol.visibility.is_public());
+    let visible_scope = symbol
+        .visibility
+        .visible_scope(&language.scope_from_path(&symbol.file));
+    let symbol_target = workspace.target_of(&symbol.file);
+    // A method of a trait can be called on values of the types implementing it.
+    let implementor_scopes: Vec<Vec<String>> = index
+        .implementations_of(symbol)
         .into_iter()
-        .filter_map(Result::ok)
-        .filter(|e| e.path().extension().map(|ext| ext == "rs").unwrap_or(false))
-    {
-        let path = entry.path();
-        let source_code = match fs::read_to_string(path) {
-            Ok(code) => code,
-            Err(_) => continue,
-        };
-        let tree = match language.parse(&source_code).ok() {
-            Some(tree) => tree,
-            None => continue,
-        };
-        let root_node = tree.root_node();
+        .filter(|implementation| implementation.kind == SymbolKind::TraitImpl)
+        .filter_map(|implementation| {
+            let mut scope =
+                workspace.absolute_scope(&implementation.file, &implementation.static_scope()?);
+            scope.push(name.clone());
+            Some(scope)
+        })
+        .collect();
+    let is_method = index.is_method(symbol);
 
-        eprintln!(
-            "DEBUGPRINT[64]: usage.rs:417: path_string={:#?}",
-            path.to_str().unwrap_or_default()
-        );
-        let mut use_map = HashMap::new();
-        if let Err(error) = extract_use_map(
-            root_node,
-            &path.to_path_buf(),
-            &source_code,
-            &mut use_map,
-            &language,
-        ) {
-            println!("Error: {:?}", error);
+    for (file, file_index) in &index.files {
+        if let Some(scanned_packages) = &scanned_packages {
+            let package = workspace.package_of(file);
+            if !package.is_some_and(|package| scanned_packages.iter().any(|p| p.dir == package.dir))
+            {
+                continue;
+            }
         }
-
-        let Ok(mut used_symbols) = extract_identifiers( tree.root_node(), &path.to_path_buf(), &source_code, language) else {
-            println!("Error");
-            return usages;
-        };
-        for used_symbol in used_symbols.iter_mut() {
-            if let Some(imported_symbol) = used_symbol.scope.first().and_then(|v| use_map.get(v)) {
-                let mut to_add = imported_symbol.scope.clone();
-                to_add.pop();
-                used_symbol.scope.splice(0..0, to_add);
+        if let Some(visible_scope) = &visible_scope {
+            let is_same_crate = match (symbol_target, workspace.target_of(file)) {
+                (Some(symbol_target), Some(target)) => symbol_target.root == target.root,
+                _ => true,
+            };
+            if !is_same_crate || !language.scope_from_path(file).starts_with(visible_scope) {
+                continue;
             }
-            let symbol_path = symbol.file.as_path();
-            let _inserted = if symbol_path
-                .canonicalize()
-                .unwrap_or(symbol_path.to_path_buf())
-                == path.canonicalize().unwrap_or(path.to_path_buf())
+        }
+        for reference in &file_index.references {
+            let used_symbol = &reference.symbol;
+            let used_name = used_symbol.name();
+            if name != used_name
+                && static_name != Some(&used_name)
+                && !index.reexports.is_renamed(&used_name)
             {
-                usages.insert(Usage {
-                    line: used_symbol.line,
-                    file: path.to_path_buf(),
-                })
-            } else if symbol.name() == used_symbol.name() {
-                if symbol.scope == used_symbol.scope {
-                    usages.insert(Usage {
-                        line: used_symbol.line,
-                        file: path.to_path_buf(),
+                continue;
+            }
+            let used_scope = index
+                .reexports
+                .canonical(&workspace.absolute_scope(file, &used_symbol.scope));
+            // A path that is neither imported explicitly nor defined in the file may come from
+            // any of the glob imports of the file.
+            let glob_scopes: Vec<Vec<String>> = match reference.is_unresolved {
+                true => file_index
+                    .globs
+                    .iter()
+                    .map(|glob| {
+                        let mut scope = glob.scope.clone();
+                        scope.extend(used_symbol.scope.iter().cloned());
+                        index
+                            .reexports
+                            .canonical(&workspace.absolute_scope(file, &scope))
                     })
-                } else {
-                    false
+                    .collect(),
+                false => Vec::new(),
+            };
+            let is_same_scope =
+                std::iter::once(&used_scope)
+                    .chain(&glob_scopes)
+                    .any(|used_scope| {
+                        symbol_scope == *used_scope
+                            || static_scope.as_ref() == Some(used_scope)
+                            || previous_scope.as_ref() == Some(used_scope)
+                    });
+            let is_used = match used_symbol.kind {
+                // Fields accessed on a value whose type is unknown, or comes from another
+                // project, are only known by their name.
+                SymbolKind::Field => {
+                    symbol.kind == SymbolKind::Field
+                        && (is_same_scope
+                            || used_symbol.scope.len() == 1
+                            || !index
+                                .reexports
+                                .is_definition(&used_scope[..used_scope.len() - 1]))
                 }
-            } else {
-                false
+                // `macro_rules!` macros are in scope after their definition without being
+                // imported, and exported ones are at the root of their crate.
+                SymbolKind::Macro => {
+                    symbol.kind == SymbolKind::Macro
+                        && (used_symbol.scope.len() == 1
+                            || exported_macro_scope.as_ref() == Some(&used_scope)
+                            || is_same_scope)
+                }
+                // Methods called on a value whose type is unknown, or comes from another
+                // project, are only known by their name.
+                SymbolKind::Method => {
+                    is_method
+                        && (is_same_scope
+                            || implementor_scopes.contains(&used_scope)
+                            || (!strict_methods
+                                && (used_symbol.scope.len() == 1
+                                    || !index
+                                        .reexports
+                                        .is_definition(&used_scope[..used_scope.len() - 1]))))
+                }
+                _ => is_same_scope,
             };
+            if is_used {
+                usages.insert(Usage {
+                    line: used_symbol.line,
+                    file: file.clone(),
+                });
+            }
         }
     }
     usages
 }
+
+#[cfg(test)]
+mod tests {
+    use super::find_symbol_usages;
+    use crate::{index::SymbolIndex, language::get_language_for_file};
+
+    /// Finds the usages of a symbol of an index.
+    ///
+    /// ## Parameters:
+    /// * `index` (`&SymbolIndex`): Index of the project,
+    /// * `scope` (`&str`): Scope of the symbol, as `crate::module::name`.
+    ///
+    /// ## Returns:
+    /// * (`Vec<(String, usize)>`): File and line of every usage, sorted.
+    fn usages_of(index: &SymbolIndex, scope: &str) -> Vec<(String, usize)> {
+        let symbol = index.symbol(scope);
+        let language = get_language_for_file(&symbol.file, &index.workspace);
+        let mut usages: Vec<(String, usize)> = find_symbol_usages(index, &symbol, &language, false)
+            .into_iter()
+            .map(|usage| (usage.file.to_string_lossy().to_string(), usage.line))
+            .collect();
+        usages.sort();
+        usages
+    }
+
+    #[test]
+    fn bare_names_resolve_in_their_module() {
+        let index = SymbolIndex::from_sources(&[(
+            "src/lib.rs",
+            r#"pub fn helper() {}
+pub fn caller() { helper(); }
+mod other {
+    pub fn helper() {}
+    pub fn use_other() { helper(); }
+}
+mod tests {
+    use super::*;
+    fn uses_glob() { helper(); }
+}
+"#,
+        )]);
+        assert_eq!(
+            usages_of(&index, "crate::helper"),
+            [("src/lib.rs".to_string(), 2), ("src/lib.rs".to_string(), 9)]
+        );
+        assert_eq!(
+            usages_of(&index, "crate::other::helper"),
+            [("src/lib.rs".to_string(), 5)]
+        );
+    }
+
+    #[test]
+    fn methods_resolve_in_their_implementation() {
+        let index = SymbolIndex::from_sources(&[(
+            "src/lib.rs",
+            r#"pub struct A;
+pub struct B;
+impl A {
+    pub fn new() -> Self { A }
+}
+impl B {
+    pub fn new() -> Self { B }
+}
+pub fn make_a() -> A { A::new() }
+pub fn make_b() -> B { B::new() }
+"#,
+        )]);
+        assert_eq!(
+            usages_of(&index, "crate::A::new"),
+            [("src/lib.rs".to_string(), 9)]
+        );
+        assert_eq!(
+            usages_of(&index, "crate::B::new"),
+            [("src/lib.rs".to_string(), 10)]
+        );
+    }
+
+    #[test]
+    fn reexported_items_are_used_through_their_new_path() {
+        let index = SymbolIndex::from_sources(&[
+            (
+                "src/lib.rs",
+                r#"mod parser;
+pub use parser::parse;
+pub fn run() { parse(); }
+"#,
+            ),
+            (
+                "src/parser.rs",
+                r#"pub fn parse() {}
+pub fn parse_twice() { parse(); parse(); }
+"#,
+            ),
+            (
+                "src/cli.rs",
+                r#"use crate::parse;
+pub fn main() { parse(); }
+"#,
+            ),
+        ]);
+        assert_eq!(
+            usages_of(&index, "crate::parser::parse"),
+            [
+                ("src/cli.rs".to_string(), 1),
+                ("src/cli.rs".to_string(), 2),
+                ("src/lib.rs".to_string(), 2),
+                ("src/lib.rs".to_string(), 3),
+                ("src/parser.rs".to_string(), 2),
+            ]
+        );
+    }
+}
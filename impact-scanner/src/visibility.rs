@@ -0,0 +1,90 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(PartialEq, Eq, Hash, Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+/// Where a symbol can be used from.
+pub enum Visibility {
+    /// Usable in the module defining it and its submodules only.
+    #[default]
+    Private,
+    /// Usable from anywhere, including other crates (`pub`).
+    Public,
+    /// Usable from anywhere in its crate (`pub(crate)`).
+    Crate,
+    /// Usable from the parent of the module defining it (`pub(super)`).
+    Super,
+    /// Usable from the given module (`pub(in path)`), as written in the source.
+    InPath(Vec<String>),
+}
+
+impl Visibility {
+    /// Parses a visibility modifier.
+    ///
+    /// ## Parameters:
+    /// * `modifier` (`&str`): Content of the modifier (eg. `pub(crate)`).
+    ///
+    /// ## Returns:
+    /// * (`Visibility`): Visibility given by the modifier, `Private` for `pub(self)`.
+    pub fn parse(modifier: &str) -> Self {
+        let modifier: String = modifier.split_whitespace().collect();
+        if modifier == "crate" {
+            return Visibility::Crate;
+        }
+        let Some(restriction) = modifier
+            .strip_prefix("pub(")
+            .and_then(|rest| rest.strip_suffix(')'))
+        else {
+            return Visibility::Public;
+        };
+        match restriction {
+            "crate" => Visibility::Crate,
+            "super" => Visibility::Super,
+            "self" => Visibility::Private,
+            _ => match restriction.strip_prefix("in") {
+                Some(path) => Visibility::InPath(path.split("::").map(str::to_string).collect()),
+                None => Visibility::Public,
+            },
+        }
+    }
+
+    /// Returns true iff the symbol is usable from other crates.
+    ///
+    /// ## Returns:
+    /// * (`bool`): true iff the visibility is `Public`.
+    pub fn is_public(&self) -> bool {
+        *self == Visibility::Public
+    }
+
+    /// Module whose files are the only ones able to use the symbol, with their submodules.
+    ///
+    /// ## Parameters:
+    /// * `module` (`&[String]`): Scope of the module defining the symbol.
+    ///
+    /// ## Returns:
+    /// * (`Option<Vec<String>>`): Scope of the module, None for public symbols.
+    pub fn visible_scope(&self, module: &[String]) -> Option<Vec<String>> {
+        let parent = || module[..module.len().saturating_sub(1)].to_vec();
+        match self {
+            Visibility::Public => None,
+            Visibility::Private => Some(module.to_vec()),
+            Visibility::Crate => Some(vec!["crate".to_string()]),
+            Visibility::Super => Some(parent()),
+            Visibility::InPath(path) => {
+                let (mut scope, skipped) = match path.first().map(String::as_str) {
+                    Some("crate") => (vec!["crate".to_string()], 1),
+                    _ => (module.to_vec(), 0),
+                };
+                for segment in path.iter().skip(skipped) {
+                    match segment.as_str() {
+                        "self" => (),
+                        "super" => {
+                            scope.pop();
+                        }
+                        _ => scope.push(segment.clone()),
+                    }
+                }
+                Some(scope)
+            }
+        }
+    }
+}
@@ -0,0 +1,653 @@
+use serde::Deserialize;
+use std::{
+    collections::BTreeSet,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{git, module_tree::ModuleTree};
+
+/// Directories where cargo discovers targets, each file or subdirectory being its own crate.
+const TARGET_DIRECTORIES: [(&str, TargetKind); 4] = [
+    ("src/bin", TargetKind::Bin),
+    ("tests", TargetKind::Test),
+    ("examples", TargetKind::Example),
+    ("benches", TargetKind::Bench),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+/// Kind of target of a package, ordered by priority when several targets share a directory.
+pub enum TargetKind {
+    /// Benchmark, in `benches/`.
+    Bench,
+    /// Example, in `examples/`.
+    Example,
+    /// Integration test, in `tests/`.
+    Test,
+    /// Binary, `src/main.rs` or in `src/bin/`.
+    Bin,
+    /// Library, `src/lib.rs`.
+    Lib,
+}
+
+#[derive(Debug, Clone)]
+/// Crate compiled from a package.
+///
+/// ## Properties:
+/// * `kind` (`TargetKind`): Kind of target,
+/// * `name` (`String`): Name of the crate, as used in paths (eg. `impact_scanner`),
+/// * `root` (`std::path::PathBuf`): Root file of the crate, relative to the project root.
+pub struct Target {
+    /// Kind of target.
+    pub kind: TargetKind,
+    /// Name of the crate, as used in paths.
+    pub name: String,
+    /// Root file of the crate, relative to the project root.
+    pub root: PathBuf,
+}
+
+impl Target {
+    /// Directory containing the modules of the crate.
+    ///
+    /// ## Returns:
+    /// * (`&std::path::Path`): Directory of the root file of the crate.
+    pub fn module_dir(&self) -> &Path {
+        self.root.parent().unwrap_or(Path::new(""))
+    }
+
+    /// Name of the crate in absolute scopes, unique in the workspace, as the library and the
+    /// binaries of a package share the same name.
+    ///
+    /// ## Returns:
+    /// * (`String`): Root file of the crate, relative to the project root.
+    pub fn scope_name(&self) -> String {
+        self.root.to_string_lossy().replace('\\', "/")
+    }
+}
+
+#[derive(Debug, Clone)]
+/// Dependency of a package.
+///
+/// ## Properties:
+/// * `name` (`String`): Name of the dependency in the code of the package,
+/// * `package` (`String`): Name of the package depended upon.
+pub struct Dependency {
+    /// Name of the dependency in the code of the package.
+    pub name: String,
+    /// Name of the package depended upon.
+    pub package: String,
+}
+
+#[derive(Debug, Clone)]
+/// Package of a workspace.
+///
+/// ## Properties:
+/// * `name` (`String`): Name of the package,
+/// * `dir` (`std::path::PathBuf`): Directory of the manifest, relative to the project root,
+/// * `targets` (`Vec<Target>`): Crates compiled from the package,
+/// * `dependencies` (`Vec<Dependency>`): Normal, dev and build dependencies of the package.
+pub struct Package {
+    /// Name of the package.
+    pub name: String,
+    /// Directory of the manifest, relative to the project root.
+    pub dir: PathBuf,
+    /// Crates compiled from the package.
+    pub targets: Vec<Target>,
+    /// Normal, dev and build dependencies of the package.
+    pub dependencies: Vec<Dependency>,
+}
+
+impl Package {
+    /// Library crate of the package, if any.
+    ///
+    /// ## Returns:
+    /// * (`Option<&Target>`): Library target.
+    pub fn lib(&self) -> Option<&Target> {
+        self.targets
+            .iter()
+            .find(|target| target.kind == TargetKind::Lib)
+    }
+
+    /// Target whose crate contains a file.
+    /// The file is either the root of a target, or a module of the target with the deepest
+    /// module directory containing it. Targets sharing a directory are preferred by kind.
+    ///
+    /// ## Parameters:
+    /// * `file` (`&std::path::Path`): Path of the file, relative to the project root.
+    ///
+    /// ## Returns:
+    /// * (`Option<&Target>`): Owning target, None if no target directory contains the file.
+    pub fn target_of(&self, file: &Path) -> Option<&Target> {
+        if let Some(target) = self.targets.iter().find(|target| target.root == file) {
+            return Some(target);
+        }
+        self.targets
+            .iter()
+            .filter(|target| file.starts_with(target.module_dir()))
+            .max_by_key(|target| (target.module_dir().components().count(), target.kind))
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+/// `[package]` table of a manifest.
+struct ManifestPackage {
+    name: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+/// `[workspace]` table of a manifest.
+struct ManifestWorkspace {
+    #[serde(default)]
+    members: Vec<String>,
+    #[serde(default)]
+    exclude: Vec<String>,
+    #[serde(default)]
+    dependencies: toml::Table,
+}
+
+#[derive(Debug, Default, Deserialize)]
+/// `[lib]`, `[[bin]]`, `[[test]]`, `[[example]]` or `[[bench]]` table of a manifest.
+struct ManifestTarget {
+    name: Option<String>,
+    path: Option<PathBuf>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+/// Parts of a `Cargo.toml` used by the analysis.
+struct Manifest {
+    package: Option<ManifestPackage>,
+    workspace: Option<ManifestWorkspace>,
+    lib: Option<ManifestTarget>,
+    #[serde(default)]
+    bin: Vec<ManifestTarget>,
+    #[serde(default)]
+    test: Vec<ManifestTarget>,
+    #[serde(default)]
+    example: Vec<ManifestTarget>,
+    #[serde(default)]
+    bench: Vec<ManifestTarget>,
+    #[serde(default)]
+    dependencies: toml::Table,
+    #[serde(default)]
+    dev_dependencies: toml::Table,
+    #[serde(default)]
+    build_dependencies: toml::Table,
+    #[serde(default)]
+    target: toml::Table,
+}
+
+/// Files of the project after the analyzed changes, where manifests are read and targets are
+/// discovered, so that they match the sources the changed lines refer to.
+///
+/// ## Properties:
+/// * `project_root` (`&std::path::Path`): Root of the project,
+/// * `diff_source` (`&git::DiffSource`): Analyzed changes,
+/// * `files` (`Option<BTreeSet<PathBuf>>`): Paths of the files relative to the project root, None
+///   if the changes end in the working tree, whose files are read directly.
+struct ProjectFiles<'a> {
+    /// Root of the project.
+    project_root: &'a Path,
+    /// Analyzed changes.
+    diff_source: &'a git::DiffSource,
+    /// Paths of the files relative to the project root, None for the working tree.
+    files: Option<BTreeSet<PathBuf>>,
+}
+
+impl<'a> ProjectFiles<'a> {
+    /// Lists the files of the project after the analyzed changes.
+    /// The working tree is used if they can not be listed, the error being reported when the
+    /// sources are read.
+    ///
+    /// ## Parameters:
+    /// * `project_root` (`&std::path::Path`): Root of the project,
+    /// * `diff_source` (`&git::DiffSource`): Analyzed changes.
+    ///
+    /// ## Returns:
+    /// * (`ProjectFiles`): Files of the analyzed version of the project.
+    fn new(project_root: &'a Path, diff_source: &'a git::DiffSource) -> Self {
+        let files = git::list_new_files(project_root, diff_source)
+            .ok()
+            .flatten()
+            .map(|files| files.into_iter().collect());
+        ProjectFiles {
+            project_root,
+            diff_source,
+            files,
+        }
+    }
+
+    /// Reads the content of a file.
+    ///
+    /// ## Parameters:
+    /// * `file` (`&std::path::Path`): Path of the file, relative to the project root.
+    ///
+    /// ## Returns:
+    /// * (`Option<String>`): Content of the file, None if it does not exist.
+    fn read(&self, file: &Path) -> Option<String> {
+        match &self.files {
+            None => fs::read_to_string(self.project_root.join(file)).ok(),
+            Some(_) => git::read_new_file(self.project_root, self.diff_source, file)
+                .ok()
+                .flatten(),
+        }
+    }
+
+    /// Returns true iff a file exists.
+    ///
+    /// ## Parameters:
+    /// * `file` (`&std::path::Path`): Path of the file, relative to the project root.
+    ///
+    /// ## Returns:
+    /// * (`bool`): true iff the path is a file of the project.
+    fn is_file(&self, file: &Path) -> bool {
+        match &self.files {
+            None => self.project_root.join(file).is_file(),
+            Some(files) => files.contains(file),
+        }
+    }
+
+    /// Lists the entries of a directory.
+    ///
+    /// ## Parameters:
+    /// * `dir` (`&std::path::Path`): Path of the directory, relative to the project root.
+    ///
+    /// ## Returns:
+    /// * (`Vec<(PathBuf, bool)>`): Path relative to the project root of each file or
+    ///   subdirectory, sorted, and true for subdirectories.
+    fn entries(&self, dir: &Path) -> Vec<(PathBuf, bool)> {
+        let Some(files) = &self.files else {
+            let Ok(entries) = fs::read_dir(self.project_root.join(dir)) else {
+                return Vec::new();
+            };
+            let mut entries: Vec<(PathBuf, bool)> = entries
+                .filter_map(Result::ok)
+                .map(|entry| (dir.join(entry.file_name()), entry.path().is_dir()))
+                .collect();
+            entries.sort();
+            return entries;
+        };
+        let mut entries: Vec<(PathBuf, bool)> = files
+            .iter()
+            .filter_map(|file| {
+                let mut components = file.strip_prefix(dir).ok()?.components();
+                let name = components.next()?;
+                Some((dir.join(name), components.next().is_some()))
+            })
+            .collect();
+        entries.dedup();
+        entries
+    }
+}
+
+/// Reads the manifest of a directory.
+///
+/// ## Parameters:
+/// * `files` (`&ProjectFiles`): Files of the analyzed version of the project,
+/// * `dir` (`&std::path::Path`): Directory containing a `Cargo.toml`, relative to the project
+///   root.
+///
+/// ## Returns:
+/// * (`Option<Manifest>`): Parsed manifest, None if it is missing or invalid.
+fn read_manifest(files: &ProjectFiles, dir: &Path) -> Option<Manifest> {
+    let content = files.read(&dir.join("Cargo.toml"))?;
+    match toml::from_str(&content) {
+        Ok(manifest) => Some(manifest),
+        Err(error) => {
+            eprintln!(
+                "❌ Manifest {:?} gives error {error}",
+                dir.join("Cargo.toml")
+            );
+            None
+        }
+    }
+}
+
+/// Name of a crate as used in paths, where dashes are not allowed.
+///
+/// ## Parameters:
+/// * `name` (`&str`): Name of a package or target.
+///
+/// ## Returns:
+/// * (`String`): Name with dashes replaced by underscores.
+fn crate_name(name: &str) -> String {
+    name.replace('-', "_")
+}
+
+/// Lists the targets of a package, declared in its manifest or discovered by cargo's conventions.
+///
+/// ## Parameters:
+/// * `files` (`&ProjectFiles`): Files of the analyzed version of the project,
+/// * `dir` (`&std::path::Path`): Directory of the package, relative to the project root,
+/// * `name` (`&str`): Name of the package,
+/// * `manifest` (`&Manifest`): Manifest of the package.
+///
+/// ## Returns:
+/// * (`Vec<Target>`): Targets of the package, with roots relative to the project root.
+fn discover_targets(
+    files: &ProjectFiles,
+    dir: &Path,
+    name: &str,
+    manifest: &Manifest,
+) -> Vec<Target> {
+    let mut targets = Vec::new();
+    let mut add = |kind: TargetKind, name: &str, root: PathBuf| {
+        let root = dir.join(root);
+        if !targets.iter().any(|target: &Target| target.root == root) {
+            targets.push(Target {
+                kind,
+                name: crate_name(name),
+                root,
+            });
+        }
+    };
+
+    let lib = manifest.lib.as_ref();
+    let lib_name = lib.and_then(|lib| lib.name.as_deref()).unwrap_or(name);
+    match lib.and_then(|lib| lib.path.clone()) {
+        Some(path) => add(TargetKind::Lib, lib_name, path),
+        None if files.is_file(&dir.join("src/lib.rs")) => {
+            add(TargetKind::Lib, lib_name, PathBuf::from("src/lib.rs"))
+        }
+        None => (),
+    }
+    let declared = [
+        (TargetKind::Bin, &manifest.bin),
+        (TargetKind::Test, &manifest.test),
+        (TargetKind::Example, &manifest.example),
+        (TargetKind::Bench, &manifest.bench),
+    ];
+    for (kind, declared_targets) in declared {
+        for target in declared_targets {
+            let (Some(target_name), Some(path)) = (&target.name, &target.path) else {
+                continue;
+            };
+            add(kind, target_name, path.clone());
+        }
+    }
+    if files.is_file(&dir.join("src/main.rs")) {
+        add(TargetKind::Bin, name, PathBuf::from("src/main.rs"));
+    }
+    for (directory, kind) in TARGET_DIRECTORIES {
+        for (entry, is_dir) in files.entries(&dir.join(directory)) {
+            let Some(file_name) = entry.file_name().and_then(|name| name.to_str()) else {
+                continue;
+            };
+            if is_dir && files.is_file(&entry.join("main.rs")) {
+                add(
+                    kind,
+                    file_name,
+                    Path::new(directory).join(file_name).join("main.rs"),
+                );
+            } else if let Some(stem) = file_name.strip_suffix(".rs") {
+                add(kind, stem, Path::new(directory).join(file_name));
+            }
+        }
+    }
+    targets
+}
+
+/// Lists the dependencies of a package, including platform specific ones
+/// (`[target.'cfg(...)'.dependencies]`).
+///
+/// ## Parameters:
+/// * `manifest` (`&Manifest`): Manifest of the package,
+/// * `workspace_dependencies` (`&toml::Table`): `[workspace.dependencies]` table of the root
+///   manifest, declaring the dependencies inherited with `workspace = true`.
+///
+/// ## Returns:
+/// * (`Vec<Dependency>`): Dependencies, with the name of the package they were renamed from.
+fn dependencies_of(manifest: &Manifest, workspace_dependencies: &toml::Table) -> Vec<Dependency> {
+    let platform_dependencies = manifest.target.values().flat_map(|platform| {
+        ["dependencies", "dev-dependencies", "build-dependencies"]
+            .into_iter()
+            .filter_map(|table| platform.get(table)?.as_table())
+    });
+    [
+        &manifest.dependencies,
+        &manifest.dev_dependencies,
+        &manifest.build_dependencies,
+    ]
+    .into_iter()
+    .chain(platform_dependencies)
+    .flatten()
+    .map(|(name, value)| {
+        // Inherited dependencies are renamed in the root manifest.
+        let declaration = match value
+            .get("workspace")
+            .and_then(|inherited| inherited.as_bool())
+        {
+            Some(true) => workspace_dependencies.get(name).unwrap_or(value),
+            _ => value,
+        };
+        Dependency {
+            name: crate_name(name),
+            package: declaration
+                .get("package")
+                .and_then(|package| package.as_str())
+                .unwrap_or(name)
+                .to_string(),
+        }
+    })
+    .collect()
+}
+
+/// Lists the directories matching the members of a workspace.
+/// Only a trailing `*` is supported as a glob, as in `crates/*`.
+///
+/// ## Parameters:
+/// * `files` (`&ProjectFiles`): Files of the analyzed version of the project,
+/// * `workspace` (`&ManifestWorkspace`): `[workspace]` table of the root manifest.
+///
+/// ## Returns:
+/// * (`Vec<std::path::PathBuf>`): Directories of the members, relative to the project root.
+fn member_dirs(files: &ProjectFiles, workspace: &ManifestWorkspace) -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+    for member in &workspace.members {
+        match member.strip_suffix("/*") {
+            Some(parent) => dirs.extend(
+                files
+                    .entries(Path::new(parent))
+                    .into_iter()
+                    .filter(|(child, is_dir)| *is_dir && files.is_file(&child.join("Cargo.toml")))
+                    .map(|(child, _)| child),
+            ),
+            None => dirs.push(PathBuf::from(member)),
+        }
+    }
+    dirs.retain(|dir| {
+        !workspace
+            .exclude
+            .iter()
+            .any(|excluded| dir == Path::new(excluded))
+    });
+    dirs
+}
+
+#[derive(Debug, Default)]
+/// Packages of the analyzed project, read from the `Cargo.toml` files without running cargo.
+///
+/// ## Properties:
+/// * `packages` (`Vec<Package>`): Packages of the workspace, the root package included,
+/// * `modules` (`ModuleTree`): Modules of every crate of the workspace.
+pub struct Workspace {
+    /// Packages of the workspace, the root package included.
+    pub packages: Vec<Package>,
+    /// Modules of every crate of the workspace.
+    pub modules: ModuleTree,
+}
+
+impl Workspace {
+    /// Reads the root manifest of a project and the manifests of its workspace members, in the
+    /// version of the project after the analyzed changes, like the sources.
+    /// The modules of their crates are left empty, see `ModuleTree::build`.
+    /// A project without manifest gives an empty workspace.
+    ///
+    /// ## Parameters:
+    /// * `project_root` (`&std::path::Path`): Root of the project,
+    /// * `diff_source` (`&git::DiffSource`): Analyzed changes.
+    ///
+    /// ## Returns:
+    /// * (`Workspace`): Packages of the project.
+    pub fn load(project_root: &Path, diff_source: &git::DiffSource) -> Self {
+        let mut workspace = Workspace::default();
+        let files = ProjectFiles::new(project_root, diff_source);
+        let Some(root_manifest) = read_manifest(&files, Path::new("")) else {
+            return workspace;
+        };
+        let workspace_dependencies = root_manifest
+            .workspace
+            .as_ref()
+            .map(|root_workspace| root_workspace.dependencies.clone())
+            .unwrap_or_default();
+        let mut manifests = vec![(PathBuf::new(), root_manifest)];
+        if let Some(members) = &manifests[0].1.workspace {
+            for dir in member_dirs(&files, members) {
+                if let Some(manifest) = read_manifest(&files, &dir) {
+                    manifests.push((dir, manifest));
+                }
+            }
+        }
+        for (dir, manifest) in manifests {
+            let Some(package) = &manifest.package else {
+                continue;
+            };
+            workspace.packages.push(Package {
+                targets: discover_targets(&files, &dir, &package.name, &manifest),
+                dependencies: dependencies_of(&manifest, &workspace_dependencies),
+                name: package.name.clone(),
+                dir,
+            });
+        }
+        workspace
+    }
+
+    /// Package containing a file, the one with the deepest directory if packages are nested.
+    ///
+    /// ## Parameters:
+    /// * `file` (`&std::path::Path`): Path of the file, relative to the project root.
+    ///
+    /// ## Returns:
+    /// * (`Option<&Package>`): Owning package, None if the file is outside of every package.
+    pub fn package_of(&self, file: &Path) -> Option<&Package> {
+        self.packages
+            .iter()
+            .filter(|package| file.starts_with(&package.dir))
+            .max_by_key(|package| package.dir.components().count())
+    }
+
+    /// Package with the given name.
+    ///
+    /// ## Parameters:
+    /// * `name` (`&str`): Name of the package.
+    ///
+    /// ## Returns:
+    /// * (`Option<&Package>`): Package, None if it is not a member of the workspace.
+    pub fn package_named(&self, name: &str) -> Option<&Package> {
+        self.packages.iter().find(|package| package.name == name)
+    }
+
+    /// Packages of the workspace depending directly on a package.
+    ///
+    /// ## Parameters:
+    /// * `name` (`&str`): Name of the package depended upon.
+    ///
+    /// ## Returns:
+    /// * (`Vec<&Package>`): Members having the package as normal, dev or build dependency.
+    pub fn dependents_of(&self, name: &str) -> Vec<&Package> {
+        self.packages
+            .iter()
+            .filter(|package| {
+                package
+                    .dependencies
+                    .iter()
+                    .any(|dependency| dependency.package == name)
+            })
+            .collect()
+    }
+
+    /// Packages where a symbol defined in a file may be used: its own package and, if the symbol
+    /// is public, the packages depending directly on it. Usages in packages depending on those
+    /// are found through the symbols using it.
+    ///
+    /// ## Parameters:
+    /// * `file` (`&std::path::Path`): File defining the symbol, relative to the project root,
+    /// * `is_public` (`bool`): true iff the symbol is usable from outside of its crate.
+    ///
+    /// ## Returns:
+    /// * (`Option<Vec<&Package>>`): Packages to scan, None if the file is outside of every
+    ///   package and the whole project must be scanned.
+    pub fn packages_using(&self, file: &Path, is_public: bool) -> Option<Vec<&Package>> {
+        let package = self.package_of(file)?;
+        let mut packages = vec![package];
+        if is_public {
+            packages.extend(self.dependents_of(&package.name));
+        }
+        Some(packages)
+    }
+
+    /// Target whose crate contains a file: the crate whose root reaches the file through `mod`
+    /// declarations, or the one guessed from the directory of the file if none does.
+    ///
+    /// ## Parameters:
+    /// * `file` (`&std::path::Path`): Path of the file, relative to the project root.
+    ///
+    /// ## Returns:
+    /// * (`Option<&Target>`): Owning target, None if the file is outside of every target.
+    pub fn target_of(&self, file: &Path) -> Option<&Target> {
+        if let Some(module) = self.modules.module_of(file) {
+            if let Some(target) = self
+                .packages
+                .iter()
+                .flat_map(|package| &package.targets)
+                .find(|target| target.root == module.root)
+            {
+                return Some(target);
+            }
+        }
+        self.package_of(file)?.target_of(file)
+    }
+
+    /// Replaces the leading `crate` of a scope used in a file by the scope name of its crate,
+    /// and the libraries used by the file, its own package's or its dependencies' (possibly
+    /// renamed in the manifest), by the scope name of their crate, so that scopes from different
+    /// crates can be compared.
+    ///
+    /// ## Parameters:
+    /// * `file` (`&std::path::Path`): File the scope is used in, relative to the project root,
+    /// * `scope` (`&[String]`): Scope to make absolute.
+    ///
+    /// ## Returns:
+    /// * (`Vec<String>`): Scope starting with the scope name of a crate when it is known.
+    pub fn absolute_scope(&self, file: &Path, scope: &[String]) -> Vec<String> {
+        let mut absolute = scope.to_vec();
+        let Some(first) = scope.first() else {
+            return absolute;
+        };
+        let target = self.target_of(file);
+        if first == "crate" {
+            if let Some(target) = target {
+                absolute[0] = target.scope_name();
+            }
+            return absolute;
+        }
+        let Some(package) = self.package_of(file) else {
+            return absolute;
+        };
+        // Binaries, tests, examples and benchmarks use the library of their package by name.
+        let own_lib = package.lib().filter(|lib| {
+            &lib.name == first && target.is_some_and(|target| target.kind != TargetKind::Lib)
+        });
+        let lib = own_lib.or_else(|| {
+            let dependency = package
+                .dependencies
+                .iter()
+                .find(|dependency| &dependency.name == first)?;
+            self.package_named(&dependency.package)?.lib()
+        });
+        if let Some(lib) = lib {
+            absolute[0] = lib.scope_name();
+        }
+        absolute
+    }
+}